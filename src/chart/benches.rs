@@ -0,0 +1,321 @@
+//! Benchmarks for large charts
+//!
+//! These are ignored by default, as they take a while to run.  Run them
+//! with `cargo test --release -- --ignored --nocapture bench`.
+//!
+//! Each benchmark checks the speedup from storing rows as runs of equal
+//! capacity, either against a copy of the previous one-value-per-cell
+//! row, or against the time taken before the change.
+
+use super::file::*;
+use super::nodes::*;
+use super::timerow::*;
+use super::web::*;
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::Rc;
+use std::time::Instant;
+
+/// Generate the config for a chart with the given number of people, and
+/// the given number of projects, each with the given number of tasks.
+fn generate_config(weeks: u32, people: u32, projects: u32, tasks: u32) -> ConfigLines {
+
+    let mut f = ConfigLines::new();
+    let mut line_num = 1;
+    let mut add_node = |f: &mut ConfigLines, indent: u32, name: &str| {
+        f.add_line(Line::new_node_line(line_num, indent, name));
+        line_num += 1;
+    };
+
+    add_node(&mut f, 1, "[chart]");
+    f.add_line(Line::new_attribute_line("weeks", &format!("{}", weeks)));
+    f.add_line(Line::new_attribute_line("today", "3"));
+
+    add_node(&mut f, 1, "[people]");
+    for p in 0..people {
+        f.add_line(Line::new_attribute_line(&format!("p{}", p), "1.."));
+    }
+
+    // A smeared overhead for everyone
+    add_node(&mut f, 1, "Overheads");
+    f.add_line(Line::new_attribute_line("resource", "smearremaining"));
+    f.add_line(Line::new_attribute_line("non-managed", "true"));
+    for p in 0..people {
+        add_node(&mut f, 2, &format!("p{}", p));
+        f.add_line(Line::new_attribute_line("plan", "25pcy"));
+        f.add_line(Line::new_attribute_line("C1.2", "1"));
+    }
+
+    let strategies = ["frontload", "smearremaining", "backload", "prodsfr"];
+    for project in 0..projects {
+        add_node(&mut f, 1, &format!("Project {}", project));
+        f.add_line(Line::new_attribute_line("resource", strategies[project as usize % 4]));
+        f.add_line(Line::new_attribute_line("non-managed", "true"));
+        if project % 2 == 0 {
+            f.add_line(Line::new_attribute_line("schedule", "serial"));
+        }
+        for task in 0..tasks {
+            add_node(&mut f, 2, &format!("Task {}", task));
+            f.add_line(Line::new_attribute_line("who",
+                                                &format!("p{}", (project * 7 + task) % people)));
+            f.add_line(Line::new_attribute_line("plan", &format!("{}", 1 + task % 10)));
+        }
+    }
+
+    f
+}
+
+/// Build, allocate and display a generated chart, returning the time taken
+fn time_chart(weeks: u32, people: u32, projects: u32, tasks: u32) -> f64 {
+
+    let mut f = generate_config(weeks, people, projects, tasks);
+    let start = Instant::now();
+
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+    let mut context = TemplateContext::new(weeks, 3, None);
+    rc_root.borrow().display_gantt(&mut context).unwrap();
+
+    let elapsed = start.elapsed();
+    elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1e9
+}
+
+/// The time taken to rebuild the largest chart in bench_chart, in a
+/// release build, before rows were stored as runs.  It now takes about
+/// half of this.
+const CELL_CHART_SECS: f64 = 0.63;
+
+#[test]
+#[ignore]
+fn bench_chart() {
+    let mut secs = 0.0;
+    for &(weeks, people, projects, tasks) in &[(10, 5, 4, 10),
+                                               (52, 50, 10, 50),
+                                               (52, 50, 20, 100)] {
+        secs = time_chart(weeks, people, projects, tasks);
+        println!("{} weeks, {} people, {} tasks: {:.3}s",
+                 weeks,
+                 people,
+                 projects * tasks,
+                 secs);
+    }
+
+    // Debug builds are too slow to compare.
+    if !cfg!(debug_assertions) {
+        assert!(secs < CELL_CHART_SECS,
+                "took {:.3}s, but took {:.3}s with a value per cell",
+                secs,
+                CELL_CHART_SECS);
+    }
+}
+
+/// The operations on a row that are timed by bench_timerow
+trait BenchRow: Sized {
+    fn new() -> Self;
+    fn set_range(&mut self, range: Range<u32>);
+    fn count_range(&self, range: Range<u32>) -> f32;
+    fn fill_transfer_to(&mut self, dest: &mut Self, count: f32, range: Range<u32>);
+    fn reverse_fill_transfer_to(&mut self, dest: &mut Self, count: f32, range: Range<u32>);
+    fn smear_transfer_to(&mut self, dest: &mut Self, count: f32, range: Range<u32>);
+
+    fn get_weekly_numbers(&self, weeks: u32) -> Vec<f32> {
+        (0..weeks).map(|week| self.count_range(week * 20..(week + 1) * 20)).collect()
+    }
+}
+
+impl BenchRow for ChartTimeRow {
+    fn new() -> ChartTimeRow {
+        ChartTimeRow::new()
+    }
+    fn set_range(&mut self, range: Range<u32>) {
+        ChartTimeRow::set_range(self, range);
+    }
+    fn count_range(&self, range: Range<u32>) -> f32 {
+        ChartTimeRow::count_range(self, range)
+    }
+    fn fill_transfer_to(&mut self, dest: &mut ChartTimeRow, count: f32, range: Range<u32>) {
+        ChartTimeRow::fill_transfer_to(self, dest, count, range);
+    }
+    fn reverse_fill_transfer_to(&mut self, dest: &mut ChartTimeRow, count: f32, range: Range<u32>) {
+        ChartTimeRow::reverse_fill_transfer_to(self, dest, count, range);
+    }
+    fn smear_transfer_to(&mut self, dest: &mut ChartTimeRow, count: f32, range: Range<u32>) {
+        ChartTimeRow::smear_transfer_to(self, dest, count, range);
+    }
+}
+
+/// Amounts smaller than this are treated as zero, as in ChartTimeRow.
+const EPSILON: f32 = 0.0001;
+
+/// A row with one value per cell, as ChartTimeRow was before it held runs
+/// of equal capacity.  The transfers work cell by cell, in the same way.
+struct CellRow {
+    cells: Vec<f32>,
+}
+
+impl CellRow {
+    fn get(&self, cell: u32) -> f32 {
+        *self.cells.get(cell as usize).unwrap_or(&0.0)
+    }
+
+    fn set_value(&mut self, cell: u32, value: f32) {
+        while self.cells.len() <= cell as usize {
+            self.cells.push(0.0);
+        }
+        self.cells[cell as usize] = value;
+    }
+
+    fn get_transferable(&self, dest: &CellRow, cell: u32, wanted: f32) -> f32 {
+        let mut amount = self.get(cell);
+        let space = 1.0 - dest.get(cell);
+        if space < amount {
+            amount = space;
+        }
+        if wanted < amount {
+            amount = wanted;
+        }
+        if amount < EPSILON { 0.0 } else { amount }
+    }
+
+    fn move_to(&mut self, dest: &mut CellRow, cell: u32, amount: f32) {
+        let remaining = self.get(cell) - amount;
+        self.set_value(cell, if remaining < EPSILON { 0.0 } else { remaining });
+        let total = dest.get(cell) + amount;
+        dest.set_value(cell, total);
+    }
+
+    fn fill_cells<I>(&mut self, dest: &mut CellRow, count: f32, cells: I)
+        where I: Iterator<Item = u32>
+    {
+        let mut to_allocate = count;
+        for cell in cells {
+            if to_allocate < EPSILON {
+                break;
+            }
+            let amount = self.get_transferable(dest, cell, to_allocate);
+            if amount > 0.0 {
+                to_allocate -= amount;
+                self.move_to(dest, cell, amount);
+            }
+        }
+    }
+}
+
+impl BenchRow for CellRow {
+    fn new() -> CellRow {
+        CellRow { cells: Vec::new() }
+    }
+
+    fn set_range(&mut self, range: Range<u32>) {
+        for cell in range {
+            self.set_value(cell, 1.0);
+        }
+    }
+
+    fn count_range(&self, range: Range<u32>) -> f32 {
+        range.fold(0.0, |count, cell| count + self.get(cell))
+    }
+
+    fn fill_transfer_to(&mut self, dest: &mut CellRow, count: f32, range: Range<u32>) {
+        self.fill_cells(dest, count, range);
+    }
+
+    fn reverse_fill_transfer_to(&mut self, dest: &mut CellRow, count: f32, range: Range<u32>) {
+        self.fill_cells(dest, count, range.rev());
+    }
+
+    fn smear_transfer_to(&mut self, dest: &mut CellRow, count: f32, range: Range<u32>) {
+
+        let candidate_cells = range.collect::<Vec<u32>>();
+        let mut allocated = 0f32;
+        let mut transferred_this_run = 1f32;
+
+        'outer_loop: while transferred_this_run > EPSILON && count - allocated > EPSILON {
+
+            let free_cells = candidate_cells.iter()
+                .filter(|cell| dest.get(**cell) < 1.0 - EPSILON)
+                .count();
+            if free_cells == 0 {
+                break;
+            }
+            let amount_per_cell = (count - allocated) / free_cells as f32;
+
+            let mut want_allocated_this_run = 0f32;
+            transferred_this_run = 0.0;
+            for cell in &candidate_cells {
+                if dest.get(*cell) >= 1.0 - EPSILON {
+                    continue;
+                }
+
+                want_allocated_this_run += amount_per_cell;
+                if want_allocated_this_run > EPSILON + transferred_this_run {
+                    let amount = self.get_transferable(dest, *cell, count - allocated);
+                    if amount == 0.0 {
+                        continue;
+                    }
+                    allocated += amount;
+                    transferred_this_run += amount;
+                    self.move_to(dest, *cell, amount);
+
+                    if count - allocated < EPSILON {
+                        break 'outer_loop;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Run the timerow workload on 100 rows, returning the time taken, and
+/// the weekly numbers left in the last person's row.
+fn time_rows<R: BenchRow>(weeks: u32) -> (f64, Vec<f32>) {
+
+    let quarters = weeks * 20;
+    let start = Instant::now();
+    let mut left = Vec::new();
+
+    for _ in 0..100 {
+        let mut person = R::new();
+        person.set_range(0..quarters);
+        let mut overhead = R::new();
+        person.smear_transfer_to(&mut overhead, weeks as f32 * 2.0, 0..quarters);
+        for task in 0..50 {
+            let mut cells = R::new();
+            person.fill_transfer_to(&mut cells, 40.0, task * 10..quarters);
+            cells.count_range(0..quarters);
+        }
+        let mut sfr = R::new();
+        person.reverse_fill_transfer_to(&mut sfr, weeks as f32 * 4.0, 0..quarters);
+        left = person.get_weekly_numbers(weeks);
+    }
+
+    let elapsed = start.elapsed();
+    (elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1e9, left)
+}
+
+#[test]
+#[ignore]
+fn bench_timerow() {
+    for &weeks in &[52, 260] {
+        let (run_secs, run_left) = time_rows::<ChartTimeRow>(weeks);
+        let (cell_secs, cell_left) = time_rows::<CellRow>(weeks);
+        println!("100 rows of {} quarters: {:.3}s, or {:.3}s with a value per cell",
+                 weeks * 20,
+                 run_secs,
+                 cell_secs);
+
+        // Both leave the same time free, although rounding can move a
+        // smeared quarter into a different week.
+        let run_total: f32 = run_left.iter().sum();
+        let cell_total: f32 = cell_left.iter().sum();
+        assert!((run_total - cell_total).abs() < 0.01,
+                "{} quarters free, but {} with a value per cell",
+                run_total,
+                cell_total);
+        assert!(run_secs < cell_secs);
+    }
+}
@@ -0,0 +1,119 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A calendar date, in the form YYYY-MM-DD
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+pub struct ChartDate {
+    year: i32,
+    month: u32,
+    day: u32,
+}
+
+impl fmt::Display for ChartDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl FromStr for ChartDate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ChartDate::new(s)
+    }
+}
+
+impl ChartDate {
+    pub fn new(desc: &str) -> Result<ChartDate, String> {
+        let v: Vec<&str> = desc.trim().split('-').collect();
+        if v.len() != 3 || v[0].len() != 4 {
+            return Err(format!("Date must be in the form YYYY-MM-DD: {}", desc));
+        }
+
+        let year = try!(v[0].parse::<i32>()
+            .map_err(|e| format!("Failed to parse year from date:{}, {}", desc, e)));
+        let month = try!(v[1].parse::<u32>()
+            .map_err(|e| format!("Failed to parse month from date:{}, {}", desc, e)));
+        let day = try!(v[2].parse::<u32>()
+            .map_err(|e| format!("Failed to parse day from date:{}, {}", desc, e)));
+
+        ChartDate::new_from_ymd(year, month, day)
+            .ok_or(format!("Date does not exist: {}", desc))
+    }
+
+    /// Create a date, if it exists
+    pub fn new_from_ymd(year: i32, month: u32, day: u32) -> Option<ChartDate> {
+        if month < 1 || month > 12 || day < 1 || day > ChartDate::days_in_month(year, month) {
+            return None;
+        }
+        Some(ChartDate {
+                 year: year,
+                 month: month,
+                 day: day,
+             })
+    }
+
+    /// Today's date, according to the system clock (UTC)
+    pub fn today() -> ChartDate {
+        let secs = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs() as i64,
+            Err(_) => 0,
+        };
+        ChartDate::new_from_days(secs / 86400)
+    }
+
+    /// Create a date from the number of days since 1970-01-01
+    pub fn new_from_days(days: i64) -> ChartDate {
+        let z = days + 719468;
+        let era = (if z >= 0 { z } else { z - 146096 }) / 146097;
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        ChartDate {
+            year: year as i32,
+            month: month as u32,
+            day: day as u32,
+        }
+    }
+
+    /// The number of days since 1970-01-01
+    pub fn get_days(&self) -> i64 {
+        let year = self.year as i64 - if self.month <= 2 { 1 } else { 0 };
+        let era = (if year >= 0 { year } else { year - 399 }) / 400;
+        let yoe = year - era * 400;
+        let month = self.month as i64;
+        let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 +
+                  self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146097 + doe - 719468
+    }
+
+    /// The day of the week, where Monday is 0 and Sunday is 6
+    pub fn get_weekday(&self) -> u32 {
+        (((self.get_days() + 3) % 7 + 7) % 7) as u32
+    }
+
+    /// The date a number of days after this one
+    pub fn add_days(&self, days: i64) -> ChartDate {
+        ChartDate::new_from_days(self.get_days() + days)
+    }
+
+    fn days_in_month(year: i32, month: u32) -> u32 {
+        match month {
+            4 | 6 | 9 | 11 => 30,
+            2 => {
+                if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 {
+                    29
+                } else {
+                    28
+                }
+            }
+            _ => 31,
+        }
+    }
+}
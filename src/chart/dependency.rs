@@ -0,0 +1,97 @@
+use super::duration::*;
+use super::DependencyType;
+use std::str::FromStr;
+
+/// A dependency on another node in the chart
+///
+/// Dependencies are described by the "after" attribute, in the form
+/// `[FS|SS|FF ]<path>[ + <lag>]`, for example `SS PRD 12345/DCUT + 2d`.
+/// - The type defaults to FS (finish-to-start).
+/// - The path is the names of the nodes, from the top level down,
+///   separated by '/'.
+/// - The lag is in days, optionally suffixed with "d".  It can be
+///   negative, eg "- 1d".
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Dependency {
+    path: String,
+    kind: DependencyType,
+    lag: Duration,
+}
+
+impl FromStr for Dependency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Dependency::new(s)
+    }
+}
+
+impl Dependency {
+    pub fn new(desc: &str) -> Result<Dependency, String> {
+
+        let mut slice = desc.trim();
+
+        // Strip off the dependency type, if there is one.
+        let mut kind = DependencyType::FinishToStart;
+        for &(prefix, k) in &[("FS ", DependencyType::FinishToStart),
+                              ("SS ", DependencyType::StartToStart),
+                              ("FF ", DependencyType::FinishToFinish)] {
+            if slice.starts_with(prefix) {
+                kind = k;
+                slice = slice[prefix.len()..].trim_left();
+                break;
+            }
+        }
+
+        // Strip off the lag, if there is one.
+        let mut lag = Duration::new_quarters(0);
+        let lag_pos = match (slice.rfind(" + "), slice.rfind(" - ")) {
+            (Some(a), Some(b)) => Some(if a > b { a } else { b }),
+            (Some(a), None) => Some(a),
+            (None, Some(b)) => Some(b),
+            (None, None) => None,
+        };
+        match lag_pos {
+            Some(pos) => {
+                let mut lag_str = slice[pos + 3..].trim();
+                if lag_str.ends_with('d') {
+                    lag_str = &lag_str[..lag_str.len() - 1];
+                }
+                let days = try!(lag_str.parse::<f32>()
+                    .map_err(|e| format!("Invalid lag in dependency {}: {}", desc, e.to_string())));
+                lag = if &slice[pos..pos + 3] == " - " {
+                    Duration::new_days(-days)
+                } else {
+                    Duration::new_days(days)
+                };
+                slice = slice[..pos].trim_right();
+            }
+            None => {}
+        };
+
+        if slice.len() == 0 {
+            return Err(format!("No node in dependency: {}", desc));
+        }
+
+        Ok(Dependency {
+               path: slice.to_string(),
+               kind: kind,
+               lag: lag,
+           })
+    }
+
+    /// The path to the node that is depended on
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    /// The type of the dependency
+    pub fn get_kind(&self) -> DependencyType {
+        self.kind
+    }
+
+    /// The lag between the two nodes
+    pub fn get_lag(&self) -> Duration {
+        self.lag
+    }
+}
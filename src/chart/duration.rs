@@ -69,40 +69,10 @@ impl Duration {
     /// - "2.25": 2.25 days, or 9 quarters
     /// - "4.5pcy": 4.5 days per calendar year
     /// - "2pcm": 2 days per calendar month
+    ///
+    /// Uncertain estimates (see Estimate) give their expected duration.
     pub fn new_from_string(amount: &str, over_num_days: &Duration) -> Result<Duration, String> {
-
-        let mut per_year = false;
-        let mut per_month = false;
-        let mut slice = amount;
-        if amount.ends_with("pcy") {
-            slice = &amount[..amount.len() - 3];
-            per_year = true;
-        }
-        if amount.ends_with("pcm") {
-            slice = &amount[..amount.len() - 3];
-            per_month = true;
-        }
-
-        match slice.parse::<f32>() {
-            Ok(number) => {
-                if per_year {
-                    // Calculate number, round to the nearest 0.25 days.
-                    Ok(Duration::new_quarters((4f32 * number * over_num_days.days() /
-                                               (5f32 * 52f32))
-                                                      .ceil() as
-                                              i32))
-                } else if per_month {
-                    // Calculate number, round to the nearest 0.25 days.
-                    Ok(Duration::new_quarters((4f32 * number * over_num_days.days() /
-                                               (5f32 * 52f32 / 12f32))
-                                                      .ceil() as
-                                              i32))
-                } else {
-                    Ok(Duration::new_days(number))
-                }
-            }
-            Err(e) => Err(e.to_string()),
-        }
+        Estimate::new_from_string(amount, over_num_days).map(|e| e.get_expected())
     }
 
     /// The number of days in this Duration
@@ -135,3 +105,111 @@ impl Duration {
         self.quarters -= quarters;
     }
 }
+
+/// An estimate of a duration, which may be uncertain
+///
+/// Supported formats are those for Duration::new_from_string, or
+/// - "8..12": anywhere between 8 and 12 days, equally likely
+/// - "6/9/15": at least 6 days, most likely 9, and at most 15.  This is
+///   a triangular distribution.
+///
+/// A pcy or pcm suffix applies to all of the values, eg "2..4pcm".
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Estimate {
+    low: f32,
+    likely: f32,
+    high: f32,
+    uniform: bool,
+    // For pcy and pcm estimates, the number of days that the values are
+    // per, and the number of days that they are spread over.
+    per: Option<(f32, f32)>,
+}
+
+impl Estimate {
+    /// Create an Estimate from a string
+    pub fn new_from_string(amount: &str, over_num_days: &Duration) -> Result<Estimate, String> {
+
+        let mut per = None;
+        let mut slice = amount;
+        if amount.ends_with("pcy") {
+            slice = &amount[..amount.len() - 3];
+            per = Some((5f32 * 52f32, over_num_days.days()));
+        }
+        if amount.ends_with("pcm") {
+            slice = &amount[..amount.len() - 3];
+            per = Some((5f32 * 52f32 / 12f32, over_num_days.days()));
+        }
+
+        let uniform = slice.contains("..");
+        let parts: Vec<&str> = if uniform {
+            slice.split("..").collect()
+        } else {
+            slice.split('/').collect()
+        };
+        let mut values = Vec::new();
+        for part in &parts {
+            match part.trim().parse::<f32>() {
+                Ok(number) => values.push(number),
+                Err(e) => return Err(e.to_string()),
+            };
+        }
+
+        let (low, likely, high) = match (values.len(), uniform) {
+            (1, false) => (values[0], values[0], values[0]),
+            (2, true) => (values[0], (values[0] + values[1]) / 2.0, values[1]),
+            (3, false) => (values[0], values[1], values[2]),
+            _ => return Err(format!("Invalid estimate {}", amount)),
+        };
+        if low > likely || likely > high {
+            return Err(format!("Estimate {} must be in increasing order", amount));
+        }
+
+        Ok(Estimate {
+               low: low,
+               likely: likely,
+               high: high,
+               uniform: uniform,
+               per: per,
+           })
+    }
+
+    /// Convert a number of days from the estimate into a Duration
+    fn to_duration(&self, days: f32) -> Duration {
+        match self.per {
+            // Calculate number, round to the nearest 0.25 days.
+            Some((per, over)) => Duration::new_quarters((4f32 * days * over / per).ceil() as i32),
+            None => Duration::new_days(days),
+        }
+    }
+
+    /// The mean of the estimate.  This is used when the chart isn't being
+    /// simulated.
+    pub fn get_expected(&self) -> Duration {
+        if self.uniform {
+            self.to_duration(self.likely)
+        } else {
+            self.to_duration((self.low + self.likely + self.high) / 3.0)
+        }
+    }
+
+    /// The duration that the given fraction, between 0 and 1, of
+    /// outcomes are within.
+    pub fn get_quantile(&self, fraction: f32) -> Duration {
+
+        let range = self.high - self.low;
+        if range <= 0.0 {
+            return self.to_duration(self.low);
+        }
+        if self.uniform {
+            return self.to_duration(self.low + fraction * range);
+        }
+
+        let lower = self.likely - self.low;
+        let upper = self.high - self.likely;
+        if fraction < lower / range {
+            self.to_duration(self.low + (fraction * range * lower).sqrt())
+        } else {
+            self.to_duration(self.high - ((1.0 - fraction) * range * upper).sqrt())
+        }
+    }
+}
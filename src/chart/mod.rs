@@ -1,25 +1,46 @@
 #![allow(dead_code)]
+// The chart code uses try! and "name: name" struct fields throughout
+#![allow(deprecated)]
+#![allow(clippy::redundant_field_names)]
 
 use std::cell::RefCell;
 use std::rc::Rc;
 
+mod date;
+mod dependency;
 mod duration;
 mod period;
+mod person;
+mod random;
+mod recurrence;
 mod timerow;
 mod readfile;
 mod nodes;
 mod file;
 mod time;
+mod timesheet;
 mod web;
+mod who;
 
 #[cfg(test)]
 mod tests;
 
-pub use self::readfile::read_config;
+#[cfg(test)]
+mod benches;
+
+pub use self::readfile::{read_config, read_timesheet};
 pub use self::nodes::ConfigNode;
+pub use self::nodes::ScenarioSummary;
+pub use self::date::*;
+pub use self::dependency::*;
 pub use self::duration::*;
+pub use self::person::*;
+pub use self::random::*;
+pub use self::recurrence::*;
 pub use self::time::*;
 pub use self::timerow::*;
+pub use self::timesheet::*;
+pub use self::who::*;
 
 #[cfg(not(test))]
 pub use self::web::serve_web;
@@ -37,6 +58,19 @@ pub enum SchedulingStrategy {
     Parallel,
 }
 
+/// Type of dependency between two nodes
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum DependencyType {
+    /// The node cannot start until the other node has finished.
+    FinishToStart,
+
+    /// The node cannot start until the other node has started.
+    StartToStart,
+
+    /// The node cannot finish until the other node has finished.
+    FinishToFinish,
+}
+
 /// Strategy for allocating the budget
 #[derive(Debug, Eq, PartialEq)]
 pub enum ResourcingStrategy {
@@ -78,10 +112,14 @@ pub enum ResourcingStrategy {
     /// ProdSFR is a special-case of SmearRemaining, where 20% of the
     /// remaining costs are smeared, and the other 80% are back-
     /// filled at the end of the period.
+    ///
+    /// The smeared percentage can be changed with "sfr-split".
     ProdSFR,
 }
 
-pub fn generate_chart_nodes() -> Result<Rc<RefCell<ConfigNode>>, String> {
+/// Generate the chart, optionally with the overrides from a what-if
+/// scenario file applied on top of the config.
+pub fn generate_chart_nodes(scenario: Option<&str>) -> Result<Rc<RefCell<ConfigNode>>, String> {
 
     // Read in the config file
     let mut f = try!(read_config(&("config.txt".to_string())));
@@ -95,6 +133,21 @@ pub fn generate_chart_nodes() -> Result<Rc<RefCell<ConfigNode>>, String> {
         try!(root.consume_config(Some(&rc_root), &mut f));
         println!("Read {} nodes", root.count_nodes());
 
+        // The timesheet is applied whenever the chart is allocated.
+        match root.get_global_config().get("timesheet") {
+            Some(filename) => root.set_timesheet(try!(read_timesheet(filename))),
+            None => {}
+        };
+
+        match scenario {
+            Some(filename) => {
+                let mut overrides = try!(read_config(filename));
+                try!(root.apply_scenario(&mut overrides)
+                         .map_err(|e| format!("Problem in {}: {}", filename, e)));
+            }
+            None => {}
+        };
+
         // Set up the resource information
         try!(root.fill_in_gantt());
 
@@ -104,3 +157,18 @@ pub fn generate_chart_nodes() -> Result<Rc<RefCell<ConfigNode>>, String> {
 
     Ok(rc_root)
 }
+
+/// Generate the chart for each of the what-if scenarios listed in the
+/// baseline chart, and summarise them.
+pub fn run_scenarios(root: &ConfigNode) -> Result<Vec<(String, ScenarioSummary)>, String> {
+
+    let mut summaries = Vec::new();
+    for (name, filename) in root.get_scenarios() {
+        let rc_scenario = try!(generate_chart_nodes(Some(&filename))
+                                   .map_err(|e| format!("Scenario {}: {}", name, e)));
+        let summary = try!(rc_scenario.borrow().get_scenario_summary());
+        summaries.push((name, summary));
+    }
+
+    Ok(summaries)
+}
@@ -1,9 +1,36 @@
 use super::super::duration::*;
 use super::super::time::*;
 use super::super::timerow::*;
-use super::super::SchedulingStrategy;
+use super::super::who::*;
+use super::super::DependencyType;
 use super::super::ResourcingStrategy;
+use super::graph::*;
+use super::optimise::*;
 use super::*;
+use std::ops::Range;
+
+/// The ways that resource can be moved from people into a task
+#[derive(Debug, Copy, Clone)]
+enum Transfer {
+    Fill,
+    ReverseFill,
+    Smear,
+}
+
+impl Transfer {
+    fn apply(&self,
+             from: &mut ChartTimeRow,
+             to: &mut ChartTimeRow,
+             count: f32,
+             range: Range<u32>)
+             -> (Option<u32>, f32, f32) {
+        match *self {
+            Transfer::Fill => from.fill_transfer_to(to, count, range),
+            Transfer::ReverseFill => from.reverse_fill_transfer_to(to, count, range),
+            Transfer::Smear => from.smear_transfer_to(to, count, range),
+        }
+    }
+}
 
 impl ConfigNode {
     /// Set up resource information in the chart
@@ -11,37 +38,128 @@ impl ConfigNode {
     /// This is only called on the root node.
     pub fn fill_in_gantt(&self) -> Result<(), String> {
 
+        // A simulation allocates the chart many times, with the plans
+        // drawn from their estimates, before it's allocated as usual.
+        let runs: u32 = try!(self.get_config_val("simulations", Some(0)));
+        if runs > 0 {
+            let seed: u64 = try!(self.get_config_val("seed", Some(1)));
+            try!(self.simulate(runs, seed));
+        }
+
+        // The optimiser searches for a better allocation than the greedy
+        // one, if it's asked to.
+        let iterations: u32 = try!(self.get_config_val("optimise", Some(0)));
+        if iterations > 0 {
+            return self.optimise(iterations);
+        }
+
+        try!(self.allocate_schedule(None));
+        Ok(())
+    }
+
+    /// Allocate all of the resource in the chart.
+    ///
+    /// By default, tasks are allocated in the order given by the task
+    /// graph.  A schedule can give a different order, and hold tasks
+    /// back.  Returns the task graph.
+    pub fn allocate_schedule(&self, schedule: Option<&Schedule>) -> Result<TaskGraph, String> {
+
         // Read in resource information ([people])
         let weeks: u32 = try!(self.get_config_val("weeks", None));
-        let start_time = try!(self.get_config_val("today", Some(ChartTime::new("1").unwrap())));
+        let start_time = try!(self.get_today());
         let mut people_hash = try!(self.get_people(weeks));
+        self.data.borrow_mut().availability = people_hash.clone();
+        self.data.borrow_mut().holidays = try!(self.get_holidays(weeks));
+
+        // External resources can be given tasks just like people, but
+        // they aren't part of the team.
+        for (who, row) in try!(self.get_external(weeks)) {
+            if people_hash.contains_key(&who) {
+                return Err(format!("{} is in both [people] and [external]", who));
+            }
+            people_hash.insert(who, row);
+        }
 
-        // Move committed resource into the cells
-        try!(self.transfer_child_committed_resource(&mut people_hash));
+        // Make sure that the plans add up
+        self.reconcile_plans(self, weeks);
+
+        // Move committed resource, including the work done from the
+        // timesheet, into the cells
+        self.transfer_timesheet_resource(&mut people_hash);
+        try!(self.transfer_child_committed_resource(self, weeks, &mut people_hash));
+
+        // Work out when each node can be worked on, and the order in
+        // which the tasks must be allocated.
+        try!(self.set_allocation_window(self, start_time));
+        let mut graph = TaskGraph::new(self);
+        match schedule {
+            Some(s) => graph.set_schedule(s),
+            None => {}
+        };
 
         // Handle all non-managed rows.  We'll then work out management
-        // spend on the resource that hasn't yet been allocated.
+        // spend on the resource that hasn't yet been allocated.  Any
+        // that have to wait for managed rows are deferred.
         let managed = true;
-        try!(self.allocate_task_resource(self, start_time, !managed, &mut people_hash));
+        let mut deferred = Vec::new();
+        try!(self.allocate_task_resource(&graph, !managed, &mut deferred, &mut people_hash));
 
         // Handle Management
         try!(self.allocate_management_resource(weeks, &mut people_hash));
 
-        // Handle all managed rows
-        try!(self.allocate_task_resource(self, start_time, managed, &mut people_hash));
+        // Handle all managed rows, and the deferred non-managed ones
+        try!(self.allocate_task_resource(&graph, managed, &mut deferred, &mut people_hash));
+
+        // Now that everything is allocated, work out how much each task
+        // can slip.
+        graph.calculate_slack();
+        self.report_missed_deadlines();
 
         // Finally, store the people resources in the root_node
         self.data.borrow_mut().people = people_hash;
 
-        Ok(())
+        Ok(graph)
     }
 
+    /// Move the committed resource for this node into its cells.
+    ///
+    /// Recurring commitments are only transferred when the owner is
+    /// available, so that, for example, a weekly meeting is skipped
+    /// during a holiday.  A task for anyone with a role is given to
+    /// someone first, so that the commitments have an owner.  A task with
+    /// several owners can't have commitments.
     pub fn transfer_local_committed_resource(&self,
+                                             root: &ConfigNode,
+                                             weeks: u32,
                                              people_hash: &mut HashMap<String, ChartTimeRow>)
                                              -> Result<(), String> {
 
+        let mut commitments: Vec<(ChartTime, Duration, bool)> = self.get_commitments()
+            .into_iter()
+            .map(|(start, duration)| (start, duration, false))
+            .collect();
+        commitments.extend(self.get_recurring_commitments(weeks)
+                               .into_iter()
+                               .map(|(start, duration)| (start, duration, true)));
+        for problem in self.check_commitments() {
+            self.add_note(&problem);
+        }
+        if commitments.len() > 0 {
+            try!(self.choose_committed_person(root, &commitments, people_hash));
+        }
+
+        // Commitments don't say who did the work, so they can only be
+        // booked to a task with a single owner.
         let valid_who: Vec<String> = people_hash.keys().map(|x| x.clone()).collect();
-        for (start, duration) in self.get_commitments() {
+        match self.get_owners(&valid_who) {
+            Ok(Some(ref owners)) if commitments.len() > 0 && owners.get_people().len() > 1 => {
+                self.add_note("Commitments are ignored on a task with several owners; record \
+                               each person's work in a timesheet");
+                return Ok(());
+            }
+            _ => {}
+        };
+        for (start, duration, recurring) in commitments {
             let who: String;
             match self.get_who(&valid_who) {
                 Ok(Some(w)) => {
@@ -49,7 +167,7 @@ impl ConfigNode {
                 }
                 Ok(None) => {
                     self.add_note("Task has commitments but no owner");
-                    return Ok(());
+                    continue;
                 }
                 Err(e) => {
                     self.add_note(&e);
@@ -57,14 +175,13 @@ impl ConfigNode {
                 }
             }
 
-            let mut node_data = self.data.borrow_mut();
-            match people_hash.get_mut(&who).unwrap().fill_transfer_to(&mut node_data.cells,
-                                                                      duration.quarters() as u32,
-                                                                      start.get_quarter()..
-                                                                      (start.get_quarter() +
-                                                                       (duration.quarters() as
-                                                                        u32))) {
-                (_, _, 0) => {
+            match self.transfer_from_people(people_hash,
+                                            &vec![who],
+                                            Transfer::Fill,
+                                            duration.quarters() as f32,
+                                            start.get_quarter()..
+                                            (start.get_quarter() + (duration.quarters() as u32))) {
+                (_, _, fail) if fail == 0.0 || recurring => {
                     continue;
                 }
 
@@ -75,7 +192,7 @@ impl ConfigNode {
                     err_string.push_str(&format!("\n  duration={:?}", duration));
                     err_string.push_str(&format!("\n  transferred={:?}", ok));
                     err_string.push_str(&format!("\n  missed={:?}", fail));
-                    node_data.add_note(&err_string);
+                    self.add_note(&err_string);
                     continue;
                 }
             }
@@ -84,14 +201,134 @@ impl ConfigNode {
         Ok(())
     }
 
+    /// Choose who does a task for anyone with a role, if it has
+    /// commitments.  This is whoever is free for the most of the committed
+    /// work, earlier people in name order being preferred.
+    fn choose_committed_person(&self,
+                               root: &ConfigNode,
+                               commitments: &Vec<(ChartTime, Duration, bool)>,
+                               people_hash: &HashMap<String, ChartTimeRow>)
+                               -> Result<(), String> {
+
+        let role = match self.get_inherited_attribute::<Who>("who") {
+            Ok(Some(owners)) => owners.get_role().map(|r| r.clone()),
+            _ => None,
+        };
+        let role = match role {
+            Some(ref role) if self.data.borrow().chosen.is_none() => role.clone(),
+            _ => return Ok(()),
+        };
+
+        let mut best: Option<(f32, String)> = None;
+        for who in try!(root.get_people_with_role(&role)) {
+            let row = match people_hash.get(&who) {
+                Some(row) => row,
+                None => continue,
+            };
+            let free = commitments.iter().fold(0.0, |sum, &(start, duration, _)| {
+                let quarters = duration.quarters() as f32;
+                let available = row.count_range(start.get_quarter()..
+                                                start.get_quarter() +
+                                                duration.quarters() as u32);
+                sum + if available < quarters { available } else { quarters }
+            });
+            if best.as_ref().map_or(true, |b| free > b.0) {
+                best = Some((free, who));
+            }
+        }
+
+        match best {
+            Some((_, who)) => {
+                self.data.borrow_mut().chosen = Some(who);
+            }
+            None => {}
+        };
+        Ok(())
+    }
+
+    /// Move the work done from the timesheet, if there is one, into the
+    /// cells of the nodes that it was done on.
+    ///
+    /// Each row's work is taken from the time it starts, up to the end of
+    /// that day if it's less than a day, so that several rows can share a
+    /// day.  Rows that don't match a task or person are noted on the root
+    /// node, and rows that overbook the person are noted on the task.
+    ///
+    /// This is only called on the root node.
+    fn transfer_timesheet_resource(&self, people_hash: &mut HashMap<String, ChartTimeRow>) {
+
+        let timesheet = match self.data.borrow().timesheet {
+            Some(ref timesheet) => timesheet.clone(),
+            None => return,
+        };
+        for problem in timesheet.get_problems() {
+            self.add_note(problem);
+        }
+
+        for row in timesheet.get_rows() {
+            let node_rc = match self.find_node_by_path(row.get_path()) {
+                Some(ref node_rc) if node_rc.borrow().is_leaf() => node_rc.clone(),
+                Some(_) => {
+                    self.add_note(&format!("Timesheet line {}: {} isn't a task",
+                                           row.get_line_num(),
+                                           row.get_path()));
+                    continue;
+                }
+                None => {
+                    self.add_note(&format!("Timesheet line {}: unknown node {}",
+                                           row.get_line_num(),
+                                           row.get_path()));
+                    continue;
+                }
+            };
+            if !people_hash.contains_key(row.get_person()) {
+                self.add_note(&format!("Timesheet line {}: unknown person {}",
+                                       row.get_line_num(),
+                                       row.get_person()));
+                continue;
+            }
+            let start = match self.new_time(row.get_time()) {
+                Ok(ct) => ct.get_quarter(),
+                Err(e) => {
+                    self.add_note(&format!("Timesheet line {}: {}", row.get_line_num(), e));
+                    continue;
+                }
+            };
+
+            let quarters = row.get_duration().quarters() as u32;
+            let end_of_day = (start / 4 + 1) * 4;
+            let end = if start + quarters > end_of_day {
+                start + quarters
+            } else {
+                end_of_day
+            };
+            let node = node_rc.borrow();
+            match node.transfer_from_people(people_hash,
+                                            &vec![row.get_person().to_string()],
+                                            Transfer::Fill,
+                                            quarters as f32,
+                                            start..end) {
+                (_, _, fail) if fail > 0.0 => {
+                    node.add_note(&format!("Timesheet line {} overbooks {} by {} days",
+                                           row.get_line_num(),
+                                           row.get_person(),
+                                           fail / 4.0));
+                }
+                _ => {}
+            };
+        }
+    }
+
     pub fn transfer_child_committed_resource(&self,
+                                             root: &ConfigNode,
+                                             weeks: u32,
                                              people_hash: &mut HashMap<String, ChartTimeRow>)
                                              -> Result<(), String> {
 
         // Now do any child nodes
         for child_rc in &self.children {
-            try!(child_rc.borrow().transfer_local_committed_resource(people_hash));
-            try!(child_rc.borrow().transfer_child_committed_resource(people_hash));
+            try!(child_rc.borrow().transfer_local_committed_resource(root, weeks, people_hash));
+            try!(child_rc.borrow().transfer_child_committed_resource(root, weeks, people_hash));
         }
 
         Ok(())
@@ -99,12 +336,169 @@ impl ConfigNode {
 
 
 
+    /// Move resource from a group of people into the cells of this node,
+    /// recording the part that each person works.
+    ///
+    /// The group works the resource as if it were one person, with each
+    /// quarter taken from the first person in the group who is free.
+    /// Returns the same as the underlying transfer.
+    fn transfer_from_people(&self,
+                            people_hash: &mut HashMap<String, ChartTimeRow>,
+                            people: &Vec<String>,
+                            transfer: Transfer,
+                            count: f32,
+                            range: Range<u32>)
+                            -> (Option<u32>, f32, f32) {
+
+        let mut data = self.data.borrow_mut();
+        let node_data = &mut *data;
+
+        let result;
+        if people.len() == 1 {
+            let contribution = node_data.contributions
+                .entry(people[0].clone())
+                .or_insert_with(ChartTimeRow::new);
+            result = transfer.apply(people_hash.get_mut(&people[0]).unwrap(),
+                                    contribution,
+                                    count,
+                                    range);
+        } else {
+            let mut pool = ChartTimeRow::new();
+            let mut before = ChartTimeRow::new();
+            for who in people {
+                pool.set_row(&people_hash[who]);
+                match node_data.contributions.get(who) {
+                    Some(c) => before.add_row(c),
+                    None => {}
+                };
+            }
+            let mut after = before.clone();
+            result = transfer.apply(&mut pool, &mut after, count, range.clone());
+
+            // Share out what was taken between the people.
+            match after.get_period(range) {
+                Some(period) => {
+                    for q in period.get_first()..period.get_last() + 1 {
+                        let mut remaining = after.get(q) - before.get(q);
+                        for who in people {
+                            let person = people_hash.get_mut(who).unwrap();
+                            if remaining <= 0.0 || !person.is_set(q) {
+                                continue;
+                            }
+                            let available = person.get(q);
+                            let amount = if available < remaining {
+                                available
+                            } else {
+                                remaining
+                            };
+                            person.set_value(q, available - amount);
+                            let contribution = node_data.contributions
+                                .entry(who.clone())
+                                .or_insert_with(ChartTimeRow::new);
+                            let current = contribution.get(q);
+                            contribution.set_value(q, current + amount);
+                            remaining -= amount;
+                        }
+                    }
+                }
+                None => {}
+            };
+        }
+
+        node_data.cells = ConfigNode::sum_contributions(&node_data.contributions);
+        result
+    }
+
+    /// Add up the work that each person does on a node
+    fn sum_contributions(contributions: &BTreeMap<String, ChartTimeRow>) -> ChartTimeRow {
+        let mut cells = ChartTimeRow::new();
+        for contribution in contributions.values() {
+            cells.add_row(contribution);
+        }
+        cells
+    }
+
     /// Gantt out future resource for this node, and all children
     ///
     /// Returns the time of the last allocation, if there was one
+    ///
+    /// If finish_after is set, the task is not allowed to finish before
+    /// that quarter.  A task for any person with a role is given to
+    /// whoever would finish it soonest.
     fn allocate_node_task_resource(&self,
                                    root: &ConfigNode,
                                    managed: bool,
+                                   finish_after: Option<u32>,
+                                   people_hash: &mut HashMap<String, ChartTimeRow>)
+                                   -> Result<Option<ChartTime>, String> {
+
+        let role = match self.get_inherited_attribute::<Who>("who") {
+            Ok(Some(owners)) => owners.get_role().map(|r| r.clone()),
+            _ => None,
+        };
+        match role {
+            Some(ref role) if self.is_leaf() && self.data.borrow().chosen.is_none() => {
+                let candidates: Vec<String> = try!(root.get_people_with_role(role))
+                    .into_iter()
+                    .filter(|who| people_hash.contains_key(who))
+                    .collect();
+                if candidates.len() == 0 {
+                    // Only complain in the pass that allocates the task.
+                    if self.get_non_managed() == Ok(!managed) {
+                        self.add_note(&format!("Nobody has the role {}", role));
+                    }
+                    return Ok(None);
+                }
+
+                // Try out each candidate, putting everything back afterwards.
+                let mut best: Option<(u32, String)> = None;
+                for who in candidates {
+                    let (cells, contributions, notes) = {
+                        let data = self.data.borrow();
+                        (data.cells.clone(), data.contributions.clone(), data.notes.clone())
+                    };
+                    self.data.borrow_mut().chosen = Some(who.clone());
+                    let mut trial_hash = people_hash.clone();
+                    try!(self.allocate_owned_task_resource(root,
+                                                           managed,
+                                                           finish_after,
+                                                           &mut trial_hash));
+
+                    let mut data = self.data.borrow_mut();
+                    match data.projected {
+                        Some(q) if best.as_ref().map_or(true, |b| q < b.0) => {
+                            best = Some((q, who));
+                        }
+                        _ => {}
+                    };
+                    data.cells = cells;
+                    data.contributions = contributions;
+                    data.notes = notes;
+                    data.allocation = None;
+                    data.projected = None;
+                    data.chosen = None;
+                }
+
+                // If nobody could be given any work, the choice is left
+                // until later.
+                match best {
+                    Some((_, who)) => {
+                        self.data.borrow_mut().chosen = Some(who);
+                    }
+                    None => {}
+                };
+            }
+            _ => {}
+        };
+
+        self.allocate_owned_task_resource(root, managed, finish_after, people_hash)
+    }
+
+    /// Gantt out future resource for this node, once its owners are known.
+    fn allocate_owned_task_resource(&self,
+                                   root: &ConfigNode,
+                                   managed: bool,
+                                   finish_after: Option<u32>,
                                    people_hash: &mut HashMap<String, ChartTimeRow>)
                                    -> Result<Option<ChartTime>, String> {
 
@@ -118,10 +512,11 @@ impl ConfigNode {
             return Ok(last_allocation);
         }
 
-        // @@@ Duration passed into get_plan needs to be the time that
-        // the user is in-plan.  This affects multiple calls.
-        match self.get_plan(&ChartTime::new(&format!("{}", weeks + 1)).unwrap(),
-                            &Duration::new_days(weeks as f32 * 5.0)) {
+        // Plans are pro-rated over the time that the owner is in the plan.
+        let availability = self.get_owner_availability(root, weeks);
+        let time_in_plan = Duration::new_quarters(availability.count().round() as i32);
+        match self.get_estimate(&ChartTime::new(&format!("{}", weeks + 1)).unwrap(),
+                                &time_in_plan) {
             Ok(Some(d)) => {
                 days_in_plan = d;
             }
@@ -161,7 +556,8 @@ impl ConfigNode {
         let days_in_chart = Duration::new_quarters(self.data
                                                        .borrow()
                                                        .cells
-                                                       .count() as
+                                                       .count()
+                                                       .round() as
                                                    i32);
         let days_to_allocate = days_in_plan - days_in_chart;
         if days_to_allocate.is_negative() {
@@ -175,10 +571,10 @@ impl ConfigNode {
 
         // If there's no owner against this node, do nothing
         let valid_who: Vec<String> = people_hash.keys().map(|x| x.clone()).collect();
-        let who: String;
-        match self.get_who(&valid_who) {
+        let owners: Who;
+        match self.get_owners(&valid_who) {
             Ok(Some(w)) => {
-                who = w;
+                owners = w;
             }
             Ok(None) => {
                 self.add_note("This task needs allocating to someone");
@@ -191,7 +587,6 @@ impl ConfigNode {
         };
 
         // Get start and end times as quarters
-        // @@@ Both times should factor in the task-owners time-in-plan
         let start_q = self.data
             .borrow()
             .start
@@ -203,92 +598,89 @@ impl ConfigNode {
             .unwrap()
             .get_quarter() + 1;
 
-        // Get allocation type
+        // Smeared resource is spread over the part of the period that the
+        // owner is in the plan.
+        let (smear_start_q, smear_end_q) =
+            match ChartPeriod::new(start_q, end_q - 1)
+                      .and_then(|p| availability.get_period(p.get_first()..p.get_last() + 1)) {
+                Some(p) => (p.get_first(), p.get_last() + 1),
+                None => (start_q, start_q),
+            };
+
+        // Work out how the resource is to be transferred, as a list of
+        // transfers, each with the quarters to transfer, the range to
+        // transfer them into and a description for any that don't fit.
+        let mut transfers: Vec<(Transfer, f32, Range<u32>, &str)> = Vec::new();
         match self.get_resourcing_strategy() {
             Ok(Some(ResourcingStrategy::Management)) => {
                 // No-op - the management row is handled out-of-band
             }
             Ok(Some(ResourcingStrategy::SmearProRata)) => {
 
-                // Work out the time to spend per quarter day on this task.
-                // @@@ Should be time for this individual, not in full plan
-                let quarters_in_plan = weeks * 20;
+                // Work out the time to spend per quarter day on this task,
+                // based on the time the owner is in the plan.
+                let quarters_in_plan = time_in_plan.quarters();
                 let time_per_quarter = days_in_plan.quarters() as f32 / (quarters_in_plan as f32);
 
                 // Work out the time to spend in the rest of the period
-                let quarters_remaining = quarters_in_plan as i32 - start_q as i32;
-                let mut time_to_spend = (quarters_remaining as f32 * time_per_quarter).ceil();
+                let quarters_remaining = availability.count_range(smear_start_q..smear_end_q);
+                let mut time_to_spend = (quarters_remaining * time_per_quarter).ceil();
 
                 // Subtract any time already committed.
                 time_to_spend -= self.data
                     .borrow()
                     .cells
-                    .count_range(start_q..end_q) as f32;
+                    .count_range(start_q..end_q);
 
                 if time_to_spend < -0.01 {
                     self.add_note(&format!("Over-committed by {} days; update plan",
                                            time_to_spend * -1.0));
                 } else {
                     // Smear the remainder.
-                    let mut node_data = self.data.borrow_mut();
-                    match people_hash.get_mut(&who)
-                              .unwrap()
-                              .smear_transfer_to(&mut node_data.cells,
-                                                 time_to_spend as u32,
-                                                 start_q..end_q) {
-                        (last, _, unallocated) if unallocated != 0 => {
-                            node_data.add_note(&format!("{} days did not fit",
-                                                        unallocated as f32 / 4.0));
-                            last_allocation = self.max_time(last_allocation, last);
-                        }
-                        (last, _, _) => {
-                            last_allocation = self.max_time(last_allocation, last);
-                        }
-                    };
+                    transfers.push((Transfer::Smear, time_to_spend, smear_start_q..smear_end_q, ""));
                 }
             }
             Ok(Some(ResourcingStrategy::SmearRemaining)) => {
-                let mut node_data = self.data.borrow_mut();
-                match people_hash.get_mut(&who)
-                          .unwrap()
-                          .smear_transfer_to(&mut node_data.cells,
-                                             days_to_allocate.quarters() as u32,
-                                             start_q..end_q) {
-                    (last, _, unallocated) if unallocated != 0 => {
-                        node_data.add_note(&format!("{} days did not fit",
-                                                    unallocated as f32 / 4.0));
-                        last_allocation = self.max_time(last_allocation, last);
-                    }
-                    (last, _, _) => {
-                        last_allocation = self.max_time(last_allocation, last);
-                    }
-                };
+                transfers.push((Transfer::Smear,
+                                days_to_allocate.quarters() as f32,
+                                smear_start_q..smear_end_q,
+                                ""));
             }
             Ok(Some(ResourcingStrategy::FrontLoad)) => {
-
-                let mut node_data = self.data.borrow_mut();
-                match people_hash.get_mut(&who)
-                          .unwrap()
-                          .fill_transfer_to(&mut node_data.cells,
-                                            days_to_allocate.quarters() as u32,
-                                            start_q..end_q) {
-                    (last, _, unallocated) if unallocated != 0 => {
-                        node_data.add_note(&format!("{} days did not fit",
-                                                    unallocated as f32 / 4.0));
-                        last_allocation = self.max_time(last_allocation, last);
-                    }
-                    (last, _, _) => {
-                        last_allocation = self.max_time(last_allocation, last);
-                    }
-                };
+                transfers.push((Transfer::Fill,
+                                days_to_allocate.quarters() as f32,
+                                start_q..end_q,
+                                ""));
             }
             Ok(Some(ResourcingStrategy::BackLoad)) => {
-                // @@@ Implement it!
-                self.add_note(&"ResourcingStrategy::BackLoad not implemented!".to_string());
+                transfers.push((Transfer::ReverseFill,
+                                days_to_allocate.quarters() as f32,
+                                start_q..end_q,
+                                ""));
             }
             Ok(Some(ResourcingStrategy::ProdSFR)) => {
-                // @@@ Implement it!
-                self.add_note(&"ResourcingStrategy::ProdSFR not implemented!".to_string());
+
+                // Split the remaining time into the smeared and back-filled parts.
+                let split = match self.get_sfr_split() {
+                    Ok(split) => split,
+                    Err(e) => {
+                        self.add_note(&e);
+                        return Ok(last_allocation);
+                    }
+                };
+                let smear_quarters = (days_to_allocate.quarters() as f32 * split as f32 / 100.0)
+                    .ceil();
+                let backfill_quarters = days_to_allocate.quarters() as f32 - smear_quarters;
+
+                // Back-fill first, so that the smeared part fits around it.
+                transfers.push((Transfer::ReverseFill,
+                                backfill_quarters,
+                                start_q..end_q,
+                                "back-filled "));
+                transfers.push((Transfer::Smear,
+                                smear_quarters,
+                                smear_start_q..smear_end_q,
+                                "smeared "));
             }
             Ok(None) => {
                 self.add_note(&"This task needs a ResourcingStrategy".to_string());
@@ -298,20 +690,110 @@ impl ConfigNode {
             }
         };
 
+        // Each group of owners does their share of every transfer.
+        let mut missing = 0.0;
+        for (transfer, quarters, range, description) in transfers {
+            let mut unallocated = 0.0;
+            for (people, share) in owners.get_groups() {
+                match self.transfer_from_people(people_hash,
+                                                &people,
+                                                transfer,
+                                                quarters * share,
+                                                range.clone()) {
+                    (_, _, fail) => {
+                        unallocated += fail;
+                    }
+                };
+            }
+            // Cells hold fractional capacity, so report to the nearest quarter.
+            let unallocated_q = unallocated.round();
+            if unallocated_q > 0.0 {
+                self.add_note(&format!("{} {}days did not fit", unallocated_q / 4.0, description));
+            }
+            missing += unallocated;
+        }
+
+        // A finish-to-finish dependency stops the task from finishing
+        // early, so if necessary move the final quarter later.  It is
+        // moved within the work of whoever did it.
+        match finish_after {
+            Some(finish_q) => {
+                let mut data = self.data.borrow_mut();
+                let node_data = &mut *data;
+                let last_q = node_data.cells.get_period(start_q..end_q).map(|p| p.get_last());
+                let who = match last_q {
+                    Some(q) if q < finish_q => {
+                        node_data.contributions
+                            .iter()
+                            .find(|&(_, c)| c.is_set(q))
+                            .map(|(w, _)| w.clone())
+                    }
+                    _ => None,
+                };
+
+                let mut moved = true;
+                match (last_q, who) {
+                    (Some(last_q), Some(who)) => {
+                        let person = people_hash.get_mut(&who).unwrap();
+                        let contribution = node_data.contributions.get_mut(&who).unwrap();
+                        let last_amount = contribution.get(last_q);
+                        contribution.fill_transfer_to(person, last_amount, last_q..last_q + 1);
+                        match person.fill_transfer_to(contribution,
+                                                      last_amount,
+                                                      finish_q..end_q) {
+                            (Some(_), _, _) => {}
+                            _ => {
+                                person.fill_transfer_to(contribution,
+                                                        last_amount,
+                                                        last_q..last_q + 1);
+                                moved = false;
+                            }
+                        };
+                    }
+                    _ => {}
+                };
+                node_data.cells = ConfigNode::sum_contributions(&node_data.contributions);
+                if !moved {
+                    node_data.add_note(&format!("Unable to finish after {}",
+                                                ChartTime::new_from_quarter(finish_q)));
+                }
+            }
+            None => {}
+        };
+
+        // Record where the future work on this node really starts and ends.
+        let allocation = self.data
+            .borrow()
+            .cells
+            .get_period(start_q..end_q);
+        self.data.borrow_mut().allocation = allocation;
+        last_allocation = self.max_time(last_allocation, allocation.map(|p| p.get_last()));
+
+        // Work out when the task is expected to finish.  Any work that
+        // didn't fit is done by the owners as soon as they can after the
+        // end of the period, and full-time after the end of the chart.
+        let mut projected = allocation.map(|p| p.get_last());
+        if missing > 0.0 {
+            let mut owners_row = ChartTimeRow::new();
+            for who in owners.get_people() {
+                owners_row.set_row(&people_hash[who]);
+            }
+            let mut scratch = ChartTimeRow::new();
+            projected = match owners_row.fill_transfer_to(&mut scratch,
+                                                          missing,
+                                                          end_q..weeks * 20) {
+                (_, _, left) if left > 0.0 => Some(weeks * 20 - 1 + left.ceil() as u32),
+                (last, _, _) => last,
+            };
+        }
+        self.data.borrow_mut().projected = projected;
+
         return Ok(last_allocation);
     }
 
-    /// Gantt out future resource for this node, and all children
-    ///
-    /// Returns the time of the last allocation, if there was one
-    fn allocate_task_resource(&self,
-                              root: &ConfigNode,
-                              start_time: ChartTime,
-                              managed: bool,
-                              people_hash: &mut HashMap<String, ChartTimeRow>)
-                              -> Result<Option<ChartTime>, String> {
+    /// Set up the times that this node, and all children, can be worked on.
+    fn set_allocation_window(&self, root: &ConfigNode, start_time: ChartTime) -> Result<(), String> {
 
-        let mut last_allocation: Option<ChartTime> = None;
         let weeks: u32 = try!(root.get_config_val("weeks", None));
 
         // Ensure that a start and end time are set up for the allocation.
@@ -328,6 +810,17 @@ impl ConfigNode {
         };
         self.data.borrow_mut().update_start(earliest_ct);
 
+        // A task with a start time can't start before then.
+        match self.get_start() {
+            Ok(Some(ct)) => {
+                self.data.borrow_mut().update_start(ct);
+            }
+            Err(e) => {
+                self.add_note(&e);
+            }
+            _ => {}
+        };
+
         self.data.borrow_mut().update_end(ChartTime::new(&format!("{}.5.4", weeks)).unwrap());
         let mut latest_ct = self.data
             .borrow_mut()
@@ -344,48 +837,126 @@ impl ConfigNode {
         };
         self.data.borrow_mut().update_end(latest_ct);
 
-        // Do resource allocation on the local node.
-        last_allocation =
-            self.max_time_ct(last_allocation,
-                             try!(self.allocate_node_task_resource(root, managed, people_hash)));
+        // Children can't start before their parent.
+        let child_start_time = self.data
+            .borrow()
+            .start
+            .unwrap();
+        for child_rc in &self.children {
+            try!(child_rc.borrow().set_allocation_window(root, child_start_time));
+        }
 
-        // Work out whether to serialise the children.
-        let mut scheduling_serial = false;
-        match self.get_scheduling_strategy() {
-            Ok(SchedulingStrategy::Serial) => {
-                scheduling_serial = true;
-            }
-            Err(e) => {
-                self.add_note(&e);
-            }
-            _ => {}
-        };
+        Ok(())
+    }
 
-        // Allocate resource in all the children
-        for child_rc in &self.children {
+    /// Whether any task in this node, or its children, has work in its
+    /// plan that isn't in its cells.
+    fn has_unallocated_work(&self, root: &ConfigNode, weeks: u32) -> bool {
+
+        if !self.is_leaf() {
+            return self.children.iter().any(|c| c.borrow().has_unallocated_work(root, weeks));
+        }
+
+        let time_in_plan =
+            Duration::new_quarters(self.get_owner_availability(root, weeks).count().round() as
+                                   i32);
+        match self.get_estimate(&ChartTime::new(&format!("{}", weeks + 1)).unwrap(),
+                                &time_in_plan) {
+            Ok(Some(plan)) => plan.quarters() > self.data.borrow().cells.count().round() as i32,
+            _ => false,
+        }
+    }
+
+    /// Gantt out future resource for all tasks, in dependency order.
+    ///
+    /// A non-managed task that depends on work that hasn't been allocated,
+    /// such as a managed task, is deferred, and allocated along with the
+    /// managed tasks.  If the work still hasn't been allocated by then,
+    /// the dependency is ignored, and noted.
+    ///
+    /// This is only called on the root node.
+    fn allocate_task_resource(&self,
+                              graph: &TaskGraph,
+                              managed: bool,
+                              deferred: &mut Vec<usize>,
+                              people_hash: &mut HashMap<String, ChartTimeRow>)
+                              -> Result<(), String> {
 
-            // If this node is marked as serial, then all child nodes
-            // must start after any existing work is complete.
-            let mut child_start_time: ChartTime = self.data
-                .borrow()
-                .start
-                .unwrap();
-            if scheduling_serial {
-                match last_allocation {
-                    Some(ct) => {
-                        child_start_time = ChartTime::new_from_quarter(ct.get_quarter() + 1);
+        let weeks: u32 = try!(self.get_config_val("weeks", None));
+        let mut unallocated: HashMap<*const RefCell<ConfigNode>, bool> = HashMap::new();
+        for ix in graph.get_order() {
+            let task = graph.get_task(*ix).borrow();
+            let was_deferred = managed && deferred.contains(ix);
+            let allocating = task.get_non_managed() == Ok(!managed) || was_deferred;
+
+            // Tasks can't start, or finish, until the work they depend
+            // on allows it.  Work that is already done doesn't hold
+            // anything up.
+            let mut finish_after: Option<u32> = None;
+            let mut waiting = Vec::new();
+            for dep in graph.get_dependencies(*ix) {
+                let period = match dep.node.borrow().get_subtree_allocation() {
+                    Some(p) => p,
+                    None => {
+                        // Dependencies come earlier in the order, so they
+                        // don't change for the rest of this pass.
+                        let key = &*dep.node as *const RefCell<ConfigNode>;
+                        let has_work = match unallocated.get(&key) {
+                            Some(has_work) => *has_work,
+                            None => dep.node.borrow().has_unallocated_work(self, weeks),
+                        };
+                        unallocated.insert(key, has_work);
+                        if allocating && has_work {
+                            waiting.push(dep.node.borrow().get_path());
+                        }
+                        continue;
+                    }
+                };
+                let lag = dep.lag.quarters();
+                let apply_lag = |q: u32| if lag < 0 && (-lag) as u32 > q {
+                    0
+                } else {
+                    (q as i32 + lag) as u32
+                };
+                match dep.kind {
+                    DependencyType::FinishToStart => {
+                        task.data
+                            .borrow_mut()
+                            .update_start(ChartTime::new_from_quarter(apply_lag(period.get_last() +
+                                                                                1)));
+                    }
+                    DependencyType::StartToStart => {
+                        task.data
+                            .borrow_mut()
+                            .update_start(ChartTime::new_from_quarter(apply_lag(period.get_first())));
+                    }
+                    DependencyType::FinishToFinish => {
+                        let q = apply_lag(period.get_last());
+                        finish_after = match finish_after {
+                            Some(f) if f > q => Some(f),
+                            _ => Some(q),
+                        };
                     }
-                    None => {}
                 };
             }
 
-            // Allocate the child resource, update the last_allocation
-            last_allocation = self.max_time_ct(last_allocation,
-                                               try!(child_rc.borrow()
-                                    .allocate_task_resource(root,
-                                        child_start_time, managed, people_hash)));
+            if allocating && waiting.len() > 0 {
+                if !managed {
+                    deferred.push(*ix);
+                    continue;
+                }
+                for path in waiting {
+                    task.add_note(&format!("Dependency on {} was ignored, as it isn't allocated",
+                                           path));
+                }
+            }
+
+            try!(task.allocate_node_task_resource(self,
+                                                  managed && !was_deferred,
+                                                  finish_after,
+                                                  people_hash));
         }
 
-        return Ok(last_allocation);
+        Ok(())
     }
 }
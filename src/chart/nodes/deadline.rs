@@ -0,0 +1,222 @@
+use super::super::duration::*;
+use super::super::time::*;
+use super::*;
+
+/// A time that a node must start or finish by, and how far it is
+/// expected to miss it.
+///
+/// The kinds of time are
+/// - "start", when work on the node must start
+/// - "deadline", a soft limit on when the node should finish
+/// - "latest-end", a hard limit on when the node must finish.  Work isn't
+///   allocated after this time, so the expected finish allows for any
+///   work that didn't fit being done afterwards.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DeadlineReport {
+    path: String,
+    kind: String,
+    due: ChartTime,
+    projected: Option<ChartTime>,
+    missed: Duration,
+    weight: f32,
+}
+
+impl DeadlineReport {
+    /// The path of the node with the deadline
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    /// The kind of deadline, eg "latest-end"
+    pub fn get_kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// The time that the deadline is due
+    pub fn get_due(&self) -> ChartTime {
+        self.due
+    }
+
+    /// The time that the node is expected to start or finish, if there is
+    /// any work on it.
+    pub fn get_projected(&self) -> Option<ChartTime> {
+        self.projected
+    }
+
+    /// How far the deadline is expected to be missed by, or zero if it
+    /// is met.
+    pub fn get_missed(&self) -> Duration {
+        self.missed
+    }
+
+    /// How much missing the deadline matters, compared to others
+    pub fn get_weight(&self) -> f32 {
+        self.weight
+    }
+}
+
+impl ConfigNode {
+    /// Get the time that work on this node, and all children, is expected
+    /// to finish.
+    ///
+    /// This can only be used once fill_in_gantt has allocated the work.
+    pub fn get_finish(&self) -> Option<ChartTime> {
+
+        let mut finish: Option<u32> = None;
+        if self.is_leaf() {
+            let data = self.data.borrow();
+            finish = data.projected.or(data.cells.get_extent().map(|p| p.get_last()));
+        }
+        for child_rc in &self.children {
+            let child_finish = child_rc.borrow().get_finish().map(|ct| ct.get_quarter());
+            finish = match (finish, child_finish) {
+                (Some(a), Some(b)) => Some(if a > b { a } else { b }),
+                (a, None) => a,
+                (None, b) => b,
+            };
+        }
+
+        finish.map(|q| ChartTime::new_from_quarter(q))
+    }
+
+    /// Get the first quarter of work, done or future, on this node and all
+    /// children.
+    fn get_first_work(&self) -> Option<u32> {
+
+        let mut first = self.data
+            .borrow()
+            .cells
+            .get_extent()
+            .map(|p| p.get_first());
+        for child_rc in &self.children {
+            first = match (first, child_rc.borrow().get_first_work()) {
+                (Some(a), Some(b)) => Some(if a < b { a } else { b }),
+                (a, None) => a,
+                (None, b) => b,
+            };
+        }
+
+        first
+    }
+
+    /// Get a deadline that is set on this node.  Deadlines aren't
+    /// inherited.
+    fn get_deadline_time(&self, kind: &str) -> Result<Option<ChartTime>, String> {
+
+        if kind == "start" {
+            return self.get_start();
+        }
+        let time = match self.data.borrow().attributes.get(kind) {
+            Some(time) => time.clone(),
+            None => return Ok(None),
+        };
+        match self.new_time(&time) {
+            Ok(ct) => Ok(Some(ct)),
+            Err(e) => Err(self.augment_error(e)),
+        }
+    }
+
+    /// Get how much missing the deadlines on this node matters, from
+    /// "weight".  The weight isn't inherited, and defaults to 1.
+    fn get_deadline_weight(&self) -> Result<f32, String> {
+
+        match self.data.borrow().attributes.get("weight") {
+            Some(weight) => {
+                match weight.parse::<f32>() {
+                    Ok(w) if w >= 0.0 => Ok(w),
+                    _ => Err(self.augment_error(format!("Invalid weight {}", weight))),
+                }
+            }
+            None => Ok(1.0),
+        }
+    }
+
+    /// Get the deadlines that are set on this node
+    fn get_local_deadlines(&self) -> Vec<DeadlineReport> {
+
+        let weight = self.get_deadline_weight().unwrap_or(1.0);
+        let mut reports = Vec::new();
+        for kind in &["start", "deadline", "latest-end"] {
+            let due = match self.get_deadline_time(kind) {
+                Ok(Some(due)) => due,
+                _ => continue,
+            };
+            let projected = if *kind == "start" {
+                self.get_first_work().map(|q| ChartTime::new_from_quarter(q))
+            } else {
+                self.get_finish()
+            };
+            let missed = match projected {
+                Some(p) if p > due => {
+                    Duration::new_quarters(p.get_quarter() as i32 - due.get_quarter() as i32)
+                }
+                _ => Duration::new_quarters(0),
+            };
+            reports.push(DeadlineReport {
+                             path: self.get_path(),
+                             kind: kind.to_string(),
+                             due: due,
+                             projected: projected,
+                             missed: missed,
+                             weight: weight,
+                         });
+        }
+
+        reports
+    }
+
+    /// Get the deadlines set on this node and all children, in config
+    /// order.
+    pub fn get_deadline_summary(&self) -> Vec<DeadlineReport> {
+
+        let mut reports = if self.data.borrow().level == 0 {
+            Vec::new()
+        } else {
+            self.get_local_deadlines()
+        };
+        for child_rc in &self.children {
+            reports.extend(child_rc.borrow().get_deadline_summary());
+        }
+
+        reports
+    }
+
+    /// Get the total of the days that each deadline and latest end is
+    /// missed by, multiplied by its weight.
+    pub fn get_weighted_lateness(&self) -> f32 {
+
+        self.get_deadline_summary()
+            .iter()
+            .filter(|r| r.kind != "start")
+            .fold(0.0, |sum, r| sum + r.missed.days() * r.weight)
+    }
+
+    /// Add a note to this node, and all children, for each deadline that
+    /// is expected to be missed.
+    pub fn report_missed_deadlines(&self) {
+
+        if self.data.borrow().level > 0 {
+            match self.get_deadline_time("deadline") {
+                Err(e) => self.add_note(&e),
+                _ => {}
+            };
+            match self.get_deadline_weight() {
+                Err(e) => self.add_note(&e),
+                _ => {}
+            };
+            for report in self.get_local_deadlines() {
+                if report.missed.is_zero() {
+                    continue;
+                }
+                self.add_note(&format!("Misses {} of {} by {} days",
+                                       report.kind,
+                                       report.due,
+                                       report.missed.days()));
+            }
+        }
+
+        for child_rc in &self.children {
+            child_rc.borrow().report_missed_deadlines();
+        }
+    }
+}
@@ -1,8 +1,14 @@
+use super::super::date::*;
+use super::super::dependency::*;
 use super::super::duration::*;
+use super::super::person::*;
+use super::super::recurrence::*;
 use super::super::time::*;
 use super::super::timerow::*;
+use super::super::who::*;
 use super::super::SchedulingStrategy;
 use super::super::ResourcingStrategy;
+use std::ops::Range;
 use super::*;
 
 impl ConfigNode {
@@ -53,6 +59,18 @@ impl ConfigNode {
         }
     }
 
+    /// Get whether a parent's left-over plan is split between
+    /// the children that don't have a plan.
+    ///
+    /// This is inheritable, and defaults to false.
+    pub fn get_split_plan(&self) -> Result<bool, String> {
+        match self.get_inherited_attribute("split-plan") {
+            Ok(Some(val)) => Ok(val),
+            Ok(None) => Ok(false),
+            Err(e) => Err(self.augment_error(e)),
+        }
+    }
+
     /// Get the latest end time for the task.
     ///
     /// Latest end time is inheritable, and is
@@ -60,7 +78,7 @@ impl ConfigNode {
     pub fn get_latest_end(&self) -> Result<Option<ChartTime>, String> {
         match self.get_inherited_attribute::<String>("latest-end") {
             Ok(Some(ref time)) => {
-                match ChartTime::new(time) {
+                match self.new_time(time) {
                     Ok(ct) => Ok(Some(ct)),
                     Err(e) => Err(self.augment_error(e)),
                 }
@@ -70,6 +88,32 @@ impl ConfigNode {
         }
     }
 
+    /// Get the priority of the task.
+    ///
+    /// Priority is inheritable, and defaults to 0.  Tasks with a higher
+    /// priority get first call on people's time.
+    pub fn get_priority(&self) -> Result<i32, String> {
+        match self.get_inherited_attribute::<i32>("priority") {
+            Ok(Some(priority)) => Ok(priority),
+            Ok(None) => Ok(0),
+            Err(e) => Err(self.augment_error(e)),
+        }
+    }
+
+    /// Get the time that the task starts.
+    ///
+    /// Unlike the earliest start time, this is not inherited.
+    pub fn get_start(&self) -> Result<Option<ChartTime>, String> {
+        let time = match self.data.borrow().attributes.get("start") {
+            Some(time) => time.clone(),
+            None => return Ok(None),
+        };
+        match self.new_time(&time) {
+            Ok(ct) => Ok(Some(ct)),
+            Err(e) => Err(self.augment_error(e)),
+        }
+    }
+
     /// Get the earliest start time for the task.
     ///
     /// Earliest start time is inheritable, and is
@@ -77,7 +121,7 @@ impl ConfigNode {
     pub fn get_earliest_start(&self) -> Result<Option<ChartTime>, String> {
         match self.get_inherited_attribute::<String>("earliest-start") {
             Ok(Some(ref time)) => {
-                match ChartTime::new(time) {
+                match self.new_time(time) {
                     Ok(ct) => Ok(Some(ct)),
                     Err(e) => Err(self.augment_error(e)),
                 }
@@ -87,32 +131,93 @@ impl ConfigNode {
         }
     }
 
+    /// Get the dependencies of this node on other nodes.
+    ///
+    /// Dependencies are not inherited, but they apply to all of the
+    /// node's children.  Multiple dependencies are separated by ", ",
+    /// eg "PRD 12345/DCUT + 2d, SS Overheads/Hackathon".
+    pub fn get_dependencies(&self) -> Result<Vec<Dependency>, String> {
+
+        let key = "after";
+        let mut deps = Vec::new();
+        if !self.data
+                .borrow()
+                .attributes
+                .contains_key(key) {
+            return Ok(deps);
+        }
+
+        for desc in self.data.borrow().attributes[key].split(", ") {
+            deps.push(try!(Dependency::new(desc).map_err(|e| self.augment_error(e))));
+        }
+        Ok(deps)
+    }
+
+    /// Whether an attribute is a commitment.  The key of a commitment is
+    /// 'C' followed by a time, or by the weeks of a recurring commitment,
+    /// so other attributes can still start with 'C'.
+    fn is_commitment_key(key: &str) -> bool {
+        key.starts_with('C') && key[1..].chars().next().map_or(false, |c| c.is_digit(10))
+    }
+
+    /// Whether a commitment key is for a recurring commitment, eg
+    /// "C3..9/fri"
+    fn is_recurring_key(key: &str) -> bool {
+        key.contains('/') || key.contains("..")
+    }
+
+    /// Parse a one-off commitment attribute
+    fn parse_commitment(&self, key: &str, value: &str) -> Result<(ChartTime, Duration), String> {
+
+        let ct = try!(self.new_time(&key[1..])
+                          .map_err(|e| format!("Invalid commitment {}: {}", key, e)));
+        match value.parse::<f32>() {
+            Ok(d) if d > 0.0 && d.is_finite() => Ok((ct, Duration::new_days(d))),
+            _ => {
+                Err(format!("Invalid commitment {}: {} is not a positive number of days",
+                            key,
+                            value))
+            }
+        }
+    }
+
+    /// Get the sorted keys of the commitment attributes on this node
+    fn get_commitment_keys(&self) -> Vec<String> {
+
+        let mut keys: Vec<String> = self.data
+            .borrow()
+            .attributes
+            .keys()
+            .filter(|k| ConfigNode::is_commitment_key(k))
+            .map(|k| k.clone())
+            .collect();
+        keys.sort();
+        keys
+    }
+
     /// Get the resourcing commitments on this node
     ///
-    /// Commitments are not inheritable
+    /// Commitments are not inheritable.  Recurring commitments, and
+    /// commitments that can't be parsed, are left out (see
+    /// check_commitments).
     pub fn get_commitments(&self) -> Vec<(ChartTime, Duration)> {
 
         // Locate attributes that represent a commitment, and
         // build a map from ChartTime to duration.
         let mut map = BTreeMap::new();
-        for (key, value) in &self.data.borrow().attributes {
-            if key.starts_with('C') {
-                match ChartTime::new(&key[1..]) {
-                    Ok(ct) => {
-                        match value.parse::<f32>() {
-                            Ok(d) => {
-                                map.insert(ct, Duration::new_days(d));
-                            }
-                            Err(_) => {
-                                continue;
-                            }
-                        }
-                    }
-                    Err(_) => {
-                        continue;
+        for key in self.get_commitment_keys() {
+            if ConfigNode::is_recurring_key(&key) {
+                continue;
+            }
+            let value = self.data.borrow().attributes[&key].clone();
+            match self.parse_commitment(&key, &value) {
+                Ok((ct, d)) => {
+                    if !map.contains_key(&ct) {
+                        map.insert(ct, d);
                     }
                 }
-            }
+                Err(_) => {}
+            };
         }
 
         // Convert the map to an ordered vector, and return it.
@@ -123,6 +228,92 @@ impl ConfigNode {
         v
     }
 
+    /// Get the recurring commitments on this node, for each week of a
+    /// chart.  These are from "every", eg "every: mon.1 x 0.25, fri.4 x
+    /// 0.25", and from commitments with a range of weeks and a day, eg
+    /// "C3..9/fri: 0.5" (see Recurrence).
+    ///
+    /// Recurring commitments are not inheritable.  Any that can't be
+    /// parsed are left out (see check_commitments).
+    pub fn get_recurring_commitments(&self, weeks: u32) -> Vec<(ChartTime, Duration)> {
+
+        let mut recurrences = Vec::new();
+        match self.data.borrow().attributes.get("every") {
+            Some(every) => {
+                for part in every.split(", ") {
+                    recurrences.push(Recurrence::new_from_every(part));
+                }
+            }
+            None => {}
+        };
+        for key in self.get_commitment_keys() {
+            if ConfigNode::is_recurring_key(&key) {
+                recurrences.push(Recurrence::new_from_commitment(&key[1..],
+                                                                 &self.data.borrow()
+                                                                      .attributes
+                                                                      [&key]));
+            }
+        }
+
+        let mut v = Vec::new();
+        for recurrence in recurrences {
+            match recurrence {
+                Ok(r) => v.extend(r.get_commitments(weeks)),
+                Err(_) => {}
+            };
+        }
+        v.sort_by(|a, b| a.0.cmp(&b.0));
+        v
+    }
+
+    /// Get a description of each problem with the commitments on this
+    /// node, such as a day that is out of range, an amount that isn't a
+    /// number, or two commitments that start at the same time.
+    pub fn check_commitments(&self) -> Vec<String> {
+
+        let mut problems = Vec::new();
+        let mut starts: HashMap<u32, String> = HashMap::new();
+        for key in self.get_commitment_keys() {
+            let value = self.data.borrow().attributes[&key].clone();
+            if ConfigNode::is_recurring_key(&key) {
+                match Recurrence::new_from_commitment(&key[1..], &value) {
+                    Ok(_) => {}
+                    Err(e) => problems.push(self.augment_error(e)),
+                };
+                continue;
+            }
+            match self.parse_commitment(&key, &value) {
+                Ok((ct, _)) => {
+                    match starts.get(&ct.get_quarter()) {
+                        Some(other) => {
+                            problems.push(self.augment_error(format!("Commitments {} and {} \
+                                                                      start at the same time",
+                                                                     other,
+                                                                     key)));
+                            continue;
+                        }
+                        None => {}
+                    };
+                    starts.insert(ct.get_quarter(), key.clone());
+                }
+                Err(e) => problems.push(self.augment_error(e)),
+            };
+        }
+
+        match self.data.borrow().attributes.get("every") {
+            Some(every) => {
+                for part in every.split(", ") {
+                    match Recurrence::new_from_every(part) {
+                        Ok(_) => {}
+                        Err(e) => problems.push(self.augment_error(e)),
+                    };
+                }
+            }
+            None => {}
+        };
+
+        problems
+    }
 
     /// Get the strategy used to resource this node.
     ///
@@ -153,6 +344,25 @@ impl ConfigNode {
         }
     }
 
+    /// Get the percentage of ProdSFR time that is smeared.
+    ///
+    /// The rest of the time is back-filled at the end of the period.
+    /// The split is inheritable, and defaults to 20.
+    pub fn get_sfr_split(&self) -> Result<u32, String> {
+        match self.get_inherited_attribute::<u32>("sfr-split") {
+            Ok(Some(split)) => {
+                if split <= 100 {
+                    Ok(split)
+                } else {
+                    Err(self.augment_error(format!("sfr-split must be a percentage, not {}",
+                                                   split)))
+                }
+            }
+            Ok(None) => Ok(20),
+            Err(e) => Err(self.augment_error(e)),
+        }
+    }
+
     /// Get the approach used to schedule the child nodes
     ///
     /// The scheduling approach is not inherited.  By default.
@@ -202,7 +412,7 @@ impl ConfigNode {
                 use_val = val;
                 continue;
             }
-            match ChartTime::new(v2[0]) {
+            match self.new_time(v2[0]) {
                 Err(e) => {
                     return Err(self.augment_error(e));
                 }
@@ -221,9 +431,17 @@ impl ConfigNode {
         }
 
         // So, we have a value in use_val.  Try to convert it to a duration.
-        match Duration::new_from_string(&use_val, time_in_chart) {
+        // During a simulation, uncertain estimates give the duration at
+        // this node's sample point; otherwise they give the expected
+        // duration.
+        match Estimate::new_from_string(&use_val, time_in_chart) {
             Err(e) => Err(self.augment_error(e)),
-            Ok(dur) => Ok(Some(dur)),
+            Ok(estimate) => {
+                Ok(Some(match self.data.borrow().sample {
+                            Some(fraction) => estimate.get_quantile(fraction),
+                            None => estimate.get_expected(),
+                        }))
+            }
         }
     }
 
@@ -246,7 +464,11 @@ impl ConfigNode {
     ///
     /// The planned time, as well as being a number, can also be suffixed with
     /// pcy or pcm.  This function converts suffixed values into actual
-    /// durations.
+    /// durations.  Each value can also be a range or three-point estimate,
+    /// eg "8..12" or "6/9/15" (see Estimate).
+    ///
+    /// A node without a plan can be given a share of its parent's plan
+    /// when the chart is allocated (see reconcile_plans).
     pub fn get_plan(&self,
                     when: &ChartTime,
                     time_in_chart: &Duration)
@@ -271,6 +493,13 @@ impl ConfigNode {
             };
         }
 
+        // A share of the parent's plan, if it was split, stands in for
+        // a plan of its own.
+        match self.data.borrow().split_plan {
+            Some(d) => return Ok(Some(d)),
+            None => {}
+        };
+
         // Local data didn't cut it.  Next step is to look for a
         // default we can inherit.  But first, bail out if this
         // is not a leaf node.
@@ -293,6 +522,176 @@ impl ConfigNode {
         };
     }
 
+    /// Get the gains recorded against this task
+    ///
+    /// Gains are not inherited.  An example gain value is "1:-2, 3:-1, 4:2".
+    /// This would mean
+    /// - In week 1, the task slipped by 2 days
+    /// - In week 3, it slipped by another day
+    /// - In week 4, 2 days were gained back
+    ///
+    /// Recording gains leaves the planned figures unchanged.
+    pub fn get_gains(&self) -> Result<Vec<(ChartTime, Duration)>, String> {
+
+        let key = "gain";
+        let mut gains = Vec::new();
+        if !self.data
+                .borrow()
+                .attributes
+                .contains_key(key) {
+            return Ok(gains);
+        }
+
+        for val in self.data.borrow().attributes[key].split(", ") {
+            let v: Vec<&str> = val.split(":").collect();
+            if v.len() != 2 {
+                return Err(self.augment_error(format!("Invalid gain part, {} must be \
+                                                       <time>:<days>",
+                                                      val)));
+            }
+            let ct = try!(self.new_time(v[0]).map_err(|e| self.augment_error(e)));
+            let days = try!(v[1].parse::<f32>()
+                .map_err(|e| self.augment_error(format!("Invalid gain {}: {}", val, e))));
+            gains.push((ct, Duration::new_days(days)));
+        }
+        gains.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(gains)
+    }
+
+    /// Get the estimate for this task at a given time.
+    ///
+    /// The estimate is the plan at that time, less any gains recorded
+    /// up to that time.
+    pub fn get_estimate(&self,
+                        when: &ChartTime,
+                        time_in_chart: &Duration)
+                        -> Result<Option<Duration>, String> {
+
+        let timeline = try!(self.get_estimate_timeline(time_in_chart));
+        let mut estimate = None;
+        for (ct, dur) in timeline {
+            if ct > *when {
+                break;
+            }
+            estimate = Some(dur);
+        }
+
+        Ok(estimate)
+    }
+
+    /// Get the timeline of changes to the estimate for this task.
+    ///
+    /// The timeline combines the plan history with the recorded gains, and
+    /// has an entry for each time that the estimate changed, in time order.
+    pub fn get_estimate_timeline(&self,
+                                 time_in_chart: &Duration)
+                                 -> Result<Vec<(ChartTime, Duration)>, String> {
+
+        let gains = try!(self.get_gains());
+
+        // Find all of the times that the estimate could change.
+        let mut times = vec![ChartTime::new("1").unwrap()];
+        let mut plan_strings = Vec::new();
+        match self.data.borrow().attributes.get("plan") {
+            Some(plan) => plan_strings.push(plan.clone()),
+            None => {}
+        };
+        if self.is_leaf() {
+            match self.get_inherited_attribute::<String>("default-plan") {
+                Ok(Some(plan)) => plan_strings.push(plan),
+                _ => {}
+            };
+        }
+        for plan in &plan_strings {
+            for val in plan.split(", ") {
+                let v: Vec<&str> = val.split(":").collect();
+                if v.len() == 2 {
+                    match self.new_time(v[0]) {
+                        Ok(ct) => times.push(ct),
+                        Err(_) => {}
+                    };
+                }
+            }
+        }
+        for &(ct, _) in &gains {
+            times.push(ct);
+        }
+        times.sort();
+        times.dedup();
+
+        // Work out the estimate at each of those times.
+        let mut timeline: Vec<(ChartTime, Duration)> = Vec::new();
+        for ct in times {
+            let plan = match try!(self.get_plan(&ct, time_in_chart)) {
+                Some(plan) => plan,
+                None => continue,
+            };
+            let mut estimate = plan;
+            for &(gain_ct, gain) in &gains {
+                if gain_ct <= ct {
+                    estimate = estimate - gain;
+                }
+            }
+
+            let changed = match timeline.last() {
+                Some(&(_, last)) => last != estimate,
+                None => true,
+            };
+            if changed {
+                timeline.push((ct, estimate));
+            }
+        }
+
+        Ok(timeline)
+    }
+
+    /// Get the gain for this node at the end of each week.
+    ///
+    /// The gain is the original plan less the estimate at the time.  If
+    /// this node doesn't have a plan, the gains of its children are summed.
+    pub fn get_gain_trend(&self, root: &ConfigNode, weeks: u32) -> Vec<Duration> {
+
+        let time_in_plan =
+            Duration::new_quarters(self.get_owner_availability(root, weeks)
+                .count()
+                .round() as i32);
+        let mut trend = vec![Duration::new_quarters(0); weeks as usize];
+
+        match self.get_plan(&ChartTime::new("1").unwrap(), &time_in_plan) {
+            Ok(Some(original)) => {
+                let timeline = self.get_estimate_timeline(&time_in_plan).unwrap_or(Vec::new());
+                let mut changes = timeline.iter().peekable();
+                let mut estimate = None;
+                for week in 1..weeks + 1 {
+                    let when = ChartTime::new_from_quarter(week * 20 - 1);
+                    while changes.peek().map_or(false, |c| c.0 <= when) {
+                        estimate = changes.next().map(|c| c.1);
+                    }
+                    match estimate {
+                        Some(estimate) => {
+                            trend[week as usize - 1] = original - estimate;
+                        }
+                        None => {}
+                    };
+                }
+                return trend;
+            }
+            _ => {}
+        };
+
+        for child_rc in &self.children {
+            for (ix, gain) in child_rc.borrow()
+                    .get_gain_trend(root, weeks)
+                    .iter()
+                    .enumerate() {
+                trend[ix] = trend[ix] + *gain;
+            }
+        }
+
+        trend
+    }
+
     /// Get a map from people to timerows.
     ///
     /// It is an error if there are none defined, or if any are badly defined.
@@ -303,20 +702,292 @@ impl ConfigNode {
         let node_rc = weak_node.upgrade().unwrap();
         let node = node_rc.borrow();
 
+        let holidays = try!(self.get_holidays(weeks));
+
         let mut people_hash = HashMap::new();
         for (key, val) in node.data
                 .borrow()
                 .attributes
                 .iter() {
-            let ct = try!(ChartTimeRow::new_populate_range(val, weeks)
+            let mut ct = try!(PersonConfig::new(val)
+                .and_then(|p| p.get_availability(weeks, self.start_date.get()))
                 .map_err(|e| format!("Problem setting up resource for {}: {}",
                                      key,
                                      e.to_string())));
+            match holidays.get(key) {
+                Some(h) => ct.unset_row(h),
+                None => {}
+            };
             people_hash.insert(key.clone(), ct);
         }
         Ok(people_hash)
     }
 
+    /// Get the people who have a role, in name order
+    pub fn get_people_with_role(&self, role: &str) -> Result<Vec<String>, String> {
+
+        let weak_node = try!(self.find_child_with_name("[people]")
+                                 .ok_or("[people] node must exist"));
+        let node_rc = weak_node.upgrade().unwrap();
+        let node = node_rc.borrow();
+
+        let mut people = Vec::new();
+        for (key, val) in node.data
+                .borrow()
+                .attributes
+                .iter() {
+            let person = try!(PersonConfig::new(val)
+                .map_err(|e| format!("Problem setting up resource for {}: {}",
+                                     key,
+                                     e.to_string())));
+            if person.has_role(role) {
+                people.push(key.clone());
+            }
+        }
+        people.sort();
+        Ok(people)
+    }
+
+    /// Get a map from external resources to the capacity they have.
+    ///
+    /// External resources, such as outsourced work, are listed in the
+    /// optional [external] node as "<name>: <capacity>".  The capacity is
+    /// the number of tasks that the resource can work on at once, or
+    /// "unlimited".  Tasks given to external resources take elapsed time:
+    /// each day of the plan is a day in the chart.
+    pub fn get_external(&self, weeks: u32) -> Result<HashMap<String, ChartTimeRow>, String> {
+
+        let mut external_hash = HashMap::new();
+        let node_rc = match self.find_child_with_name("[external]").and_then(|n| n.upgrade()) {
+            Some(n) => n,
+            None => return Ok(external_hash),
+        };
+        let node = node_rc.borrow();
+
+        // No resource can work on more tasks than there are in the chart.
+        let unlimited = self.count_nodes() as f32;
+        for (key, val) in node.data
+                .borrow()
+                .attributes
+                .iter() {
+            let capacity = match val.trim() {
+                "unlimited" => unlimited,
+                v => {
+                    match v.parse::<u32>() {
+                        Ok(c) if c > 0 => c as f32,
+                        _ => {
+                            return Err(format!("Problem setting up external resource {}: \
+                                                capacity {} must be a positive number of \
+                                                tasks or \"unlimited\"",
+                                               key,
+                                               v))
+                        }
+                    }
+                }
+            };
+            let mut ct = ChartTimeRow::new();
+            ct.set_range(0..weeks * 20);
+            ct.scale(capacity);
+            external_hash.insert(key.clone(), ct);
+        }
+        Ok(external_hash)
+    }
+
+    /// Whether this task is given only to external resources, and has a
+    /// start time.  Such a task takes elapsed time from its start, so it
+    /// isn't held up by the tasks before it in a serial schedule.
+    pub fn has_external_start(&self, root: &ConfigNode) -> bool {
+
+        if !self.is_leaf() {
+            return false;
+        }
+        match self.get_start() {
+            Ok(Some(_)) => {}
+            _ => return false,
+        };
+        let external: Vec<String> =
+            match root.find_child_with_name("[external]").and_then(|n| n.upgrade()) {
+                Some(n) => n.borrow().data.borrow().attributes.keys().cloned().collect(),
+                None => return false,
+            };
+        match self.get_owners(&external) {
+            Ok(Some(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Get a map from people to the time they have as holiday.
+    ///
+    /// Holidays are listed in the optional [holidays] node, in the form
+    /// "<time or range>: <regions>", eg "2026-12-25: all" or
+    /// "2026-12-28..2026-12-29: uk, ie".  Holidays for the region "all"
+    /// apply to everyone.  People are tagged with a region in [people],
+    /// eg "rf: 1..10 region=uk" (see PersonConfig).
+    ///
+    /// Holidays are cut down to the weekdays in the chart, so a range
+    /// can start before the chart or end at a weekend, and a single date
+    /// at a weekend is ignored.
+    pub fn get_holidays(&self, weeks: u32) -> Result<HashMap<String, ChartTimeRow>, String> {
+
+        let weak_node = try!(self.find_child_with_name("[people]")
+                                 .ok_or("[people] node must exist"));
+        let people_rc = weak_node.upgrade().unwrap();
+        let people = people_rc.borrow();
+
+        // Work out the holidays for each region.
+        let mut regions: HashMap<String, ChartTimeRow> = HashMap::new();
+        match self.find_child_with_name("[holidays]").and_then(|n| n.upgrade()) {
+            Some(node_rc) => {
+                let node = node_rc.borrow();
+                for (key, val) in node.data
+                        .borrow()
+                        .attributes
+                        .iter() {
+                    let quarters = match try!(self.get_holiday_quarters(key, weeks)
+                        .map_err(|e| format!("Problem setting up holiday {}: {}", key, e))) {
+                        Some(q) => q,
+                        None => continue,
+                    };
+                    for region in val.split(",").map(|r| r.trim()) {
+                        regions.entry(region.to_string())
+                            .or_insert(ChartTimeRow::new())
+                            .set_range(quarters.clone());
+                    }
+                }
+            }
+            None => {}
+        };
+
+        let mut holiday_hash = HashMap::new();
+        for (key, val) in people.data
+                .borrow()
+                .attributes
+                .iter() {
+            let region = try!(PersonConfig::new(val)
+                .map_err(|e| format!("Problem setting up resource for {}: {}", key, e)))
+                .get_region();
+            let mut ct = ChartTimeRow::new();
+            for r in Some("all".to_string()).iter().chain(region.iter()) {
+                match regions.get(r) {
+                    Some(h) => ct.set_row(h),
+                    None => {}
+                };
+            }
+            holiday_hash.insert(key.clone(), ct);
+        }
+        Ok(holiday_hash)
+    }
+
+    /// Get the quarters covered by an entry in [holidays], or None if it
+    /// is outside the chart.
+    fn get_holiday_quarters(&self,
+                            key: &str,
+                            weeks: u32)
+                            -> Result<Option<Range<u32>>, String> {
+
+        let v: Vec<&str> = key.split("..").collect();
+        if v.len() > 2 {
+            return Err(format!("Too many parts in range {}", key));
+        }
+
+        let start = match try!(self.get_holiday_time(v[0], false)) {
+            Some(ct) => ct,
+            None => return Ok(None),
+        };
+        let end = match try!(self.get_holiday_time(v[v.len() - 1], true)) {
+            Some(ct) => ct,
+            None => return Ok(None),
+        };
+        let first = start.get_quarter();
+        let mut last = end.get_quarter() + end.get_duration().quarters() as u32;
+        if last > weeks * 20 {
+            last = weeks * 20;
+        }
+        if first >= last {
+            return Ok(None);
+        }
+
+        Ok(Some(first..last))
+    }
+
+    /// Get the time of the first or last day of a holiday.
+    ///
+    /// A date at the weekend is moved to the nearest weekday inside the
+    /// holiday, and a first day before the chart to the start of the
+    /// chart.  Returns None if the holiday is over before the chart
+    /// starts.
+    fn get_holiday_time(&self, desc: &str, last: bool) -> Result<Option<ChartTime>, String> {
+
+        if !desc.contains('-') || desc.contains('.') {
+            return self.new_time(desc).map(|ct| Some(ct));
+        }
+
+        let mut date = try!(ChartDate::new(desc));
+        let start = self.start_date.get();
+        match start {
+            Some(start) if date < start => {
+                if last {
+                    return Ok(None);
+                }
+                date = start;
+            }
+            _ => {}
+        };
+        let weekday = date.get_weekday() as i64;
+        if weekday > 4 {
+            date = date.add_days(if last { 4 - weekday } else { 7 - weekday });
+        }
+
+        ChartTime::new_from_date(&date, start).map(|ct| Some(ct))
+    }
+
+    /// Get the time that the owners of this task are available.
+    ///
+    /// The availability is read from the root node, so this can only be
+    /// used once fill_in_gantt has read in the people.  If the task has
+    /// no valid owner, the whole chart is treated as available.
+    pub fn get_owner_availability(&self, root: &ConfigNode, weeks: u32) -> ChartTimeRow {
+
+        let root_data = root.data.borrow();
+        let valid_who: Vec<String> = root_data.availability.keys().map(|x| x.clone()).collect();
+        match self.get_owners(&valid_who) {
+            Ok(Some(owners)) => {
+                let mut row = ChartTimeRow::new();
+                for who in owners.get_people() {
+                    row.set_row(&root_data.availability[who]);
+                }
+                row
+            }
+            _ => {
+                let mut row = ChartTimeRow::new();
+                row.set_range(0..weeks * 20);
+                row
+            }
+        }
+    }
+
+    /// Get the holidays of the owners of this task.
+    ///
+    /// With several owners, only the days that they all have off are
+    /// holidays for the task.  Like get_owner_availability, this can only
+    /// be used once fill_in_gantt has read in the people.
+    pub fn get_owner_holidays(&self, root: &ConfigNode) -> ChartTimeRow {
+
+        let root_data = root.data.borrow();
+        let valid_who: Vec<String> = root_data.holidays.keys().map(|x| x.clone()).collect();
+        match self.get_owners(&valid_who) {
+            Ok(Some(owners)) => {
+                let people = owners.get_people();
+                let mut row = root_data.holidays[&people[0]].clone();
+                for who in &people[1..] {
+                    row.retain_row(&root_data.holidays[who]);
+                }
+                row
+            }
+            _ => ChartTimeRow::new(),
+        }
+    }
+
     /// Get a configuration value
     ///
     /// This must only be called on the root node.
@@ -345,27 +1016,89 @@ impl ConfigNode {
         }
     }
 
-    /// Get the owner of this task
+    /// Get the date that the chart starts, from "start-date" in [chart]
     ///
-    /// The owner can be inherited.  If this fails, the name of the
-    /// node is returned as an owner.
-    pub fn get_who(&self, valid: &Vec<String>) -> Result<Option<String>, String> {
+    /// If the date isn't a Monday, the chart starts on the Monday before
+    /// it.
+    pub fn get_start_date(&self) -> Result<Option<ChartDate>, String> {
+        match self.get_global_config().get("start-date") {
+            Some(date) => {
+                ChartDate::new(date)
+                    .map(|d| Some(d.add_days(-(d.get_weekday() as i64))))
+                    .map_err(|e| format!("Problem parsing config start-date: {}", e))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Tell all of the nodes in the chart when it starts, so that they can
+    /// read times given as dates.
+    ///
+    /// This must only be called on the root node, whenever its config
+    /// changes.
+    pub fn update_start_date(&self) -> Result<(), String> {
+        self.start_date.set(try!(self.get_start_date()));
+        Ok(())
+    }
 
-        match self.get_inherited_attribute::<String>("who") {
-            Ok(Some(who)) => {
-                if valid.contains(&who) {
-                    return Ok(Some(who));
+    /// Get the current time, from "today" in [chart]
+    ///
+    /// Everything before this time is assumed to be committed.  If there
+    /// is no "today", but the chart has a start date, the system date is
+    /// used.  Otherwise, the chart starts today.
+    pub fn get_today(&self) -> Result<ChartTime, String> {
+        let first = ChartTime::new("1").unwrap();
+        match self.get_global_config().get("today") {
+            Some(today) => {
+                return self.new_time(today)
+                    .map_err(|e| format!("Problem parsing config today: {}", e));
+            }
+            None => {}
+        };
+
+        match self.start_date.get() {
+            Some(start) => {
+                // Weekends roll forward to the following Monday.
+                let mut date = ChartDate::today();
+                while date.get_weekday() > 4 {
+                    date = date.add_days(1);
+                }
+                if date < start {
+                    Ok(first)
                 } else {
-                    return Err(self.augment_error(format!("Unrecognised \"who\": {}", &who)));
+                    ChartTime::new_from_date(&date, Some(start))
+                }
+            }
+            None => Ok(first),
+        }
+    }
+
+    /// Get the owners of this task
+    ///
+    /// The owners can be inherited.  If there are none, the name of the
+    /// node is returned as an owner.  A task for any person with a role
+    /// has no owner until one has been chosen.
+    pub fn get_owners(&self, valid: &Vec<String>) -> Result<Option<Who>, String> {
+
+        match self.get_inherited_attribute::<Who>("who") {
+            Ok(Some(ref owners)) if owners.get_role().is_some() => {
+                match self.data.borrow().chosen {
+                    Some(ref who) if valid.contains(who) => return Ok(Some(Who::new_person(who))),
+                    _ => return Ok(None),
+                };
+            }
+            Ok(Some(owners)) => {
+                for who in owners.get_people() {
+                    if !valid.contains(who) {
+                        return Err(self.augment_error(format!("Unrecognised \"who\": {}", who)));
+                    }
                 }
+                return Ok(Some(owners));
             }
 
             Ok(None) => {
                 if valid.contains(&self.data.borrow().name) {
-                    return Ok(Some(self.data
-                                       .borrow()
-                                       .name
-                                       .clone()));
+                    return Ok(Some(Who::new_person(&self.data.borrow().name)));
                 } else {
                     return Ok(None);
                 }
@@ -377,29 +1110,81 @@ impl ConfigNode {
         };
     }
 
+    /// Get the owner of this task
+    ///
+    /// If the task has several owners, this is the first of them.
+    pub fn get_who(&self, valid: &Vec<String>) -> Result<Option<String>, String> {
+
+        self.get_owners(valid).map(|owners| owners.map(|o| o.get_people()[0].clone()))
+    }
+
+    /// Get the current estimate, and the work remaining against it, summed
+    /// over this node and all of its children.
+    ///
+    /// A leaf node uses its own estimate.  For other nodes, this is the sum
+    /// of the children, and any plan on the node itself is ignored.
+    /// Problems reading plans are reported on the rows themselves, so
+    /// are ignored here.
+    pub fn get_summed_plan(&self,
+                           root: &ConfigNode,
+                           weeks: u32,
+                           today: &ChartTime)
+                           -> (Duration, Duration) {
+
+        let mut plan = Duration::new_quarters(0);
+        let mut remaining = Duration::new_quarters(0);
+
+        if self.is_leaf() {
+            let time_in_plan =
+                Duration::new_quarters(self.get_owner_availability(root, weeks)
+                    .count()
+                    .round() as i32);
+            match self.get_estimate(&ChartTime::new(&format!("{}", weeks + 1)).unwrap(),
+                                    &time_in_plan) {
+                Ok(Some(d)) => {
+                    let done = Duration::new_quarters(self.data
+                                                          .borrow()
+                                                          .cells
+                                                          .count_range(0..today.get_quarter())
+                                                          .round() as
+                                                      i32);
+                    plan = d;
+                    remaining = d - done;
+                }
+                _ => {}
+            };
+        }
+
+        for child_rc in &self.children {
+            let (child_plan, child_remaining) =
+                child_rc.borrow().get_summed_plan(root, weeks, today);
+            plan = plan + child_plan;
+            remaining = remaining + child_remaining;
+        }
+
+        (plan, remaining)
+    }
+
     /// Get the budget, as a Duration.
     ///
     /// The buget is not inherited - it is set against a single node, then
     /// compared with the plan/gain/commitments of all children.
-    pub fn get_budget(&self) -> Option<Duration> {
+    pub fn get_budget(&self) -> Result<Option<Duration>, String> {
 
         let key = "budget";
         if !self.data
                 .borrow()
                 .attributes
                 .contains_key(key) {
-            return None;
+            return Ok(None);
         }
 
-        match self.data.borrow().attributes[key].parse::<f32>() {
-            Err(e) => {
-                println!("Invalid budget in node at line {}: {}",
-                         self.data.borrow().line_num,
-                         e.to_string());
-                return None;
-            }
-            Ok(dur) => {
-                return Some(Duration::new_days(dur));
+        let budget = self.data.borrow().attributes[key].clone();
+        match budget.parse::<f32>() {
+            Ok(dur) if dur.is_finite() => Ok(Some(Duration::new_days(dur))),
+            _ => {
+                Err(self.augment_error(format!("Invalid budget {}: not a number of days",
+                                               budget)))
             }
         }
     }
@@ -11,18 +11,15 @@ impl ConfigNode {
                                   -> Result<(), String> {
 
         // Ignore "special" nodes
-        for s in vec!["chart", "people", "rows"] {
-            if self.data.borrow().name == format!("[{}]", s) {
-                return Ok(());
-            }
+        if self.is_special() {
+            return Ok(());
         }
 
         let weeks: u32 = try!(root.get_config_val("weeks", None));
 
         // Get start time for the period to allocate.  Assume that everything
         // prior to this has been committed.
-        let start: ChartTime = try!(root.get_config_val("today",
-                                                        Some(ChartTime::new("1").unwrap())));
+        let start: ChartTime = try!(root.get_today());
         //let end: ChartTime = ChartTime::new(&format!("{}", weeks+1)).unwrap();
 
         // Work out which is the start week.
@@ -32,28 +29,34 @@ impl ConfigNode {
         let mut row = TemplateRow::new(self.data.borrow().level,
                                        self.data.borrow().line_num,
                                        &self.data.borrow().name);
+        let holidays = self.get_owner_holidays(root).get_weekly_numbers(weeks);
         let mut count = 0;
         for val in &self.data
                         .borrow()
                         .cells
                         .get_weekly_numbers(weeks) {
-            row.add_cell(*val as f32 / 4.0, count == start_week);
+            row.add_cell(*val / 4.0,
+                         count == start_week,
+                         holidays[count as usize] > 0.0);
             count += 1;
         }
 
         let done = self.data
             .borrow()
             .cells
-            .count_range(0..start.get_quarter()) as f32 / 4.0;
+            .count_range(0..start.get_quarter()) / 4.0;
         row.set_done(done);
 
-        let mut plan_now = Duration::new_days(0.0);
+        // Plans are pro-rated over the time that the owner is in the plan.
+        let time_in_plan =
+            Duration::new_quarters(self.get_owner_availability(root, weeks)
+                .count()
+                .round() as i32);
+
         match self.get_plan(&ChartTime::new(&format!("{}", weeks + 1)).unwrap(),
-                            &Duration::new_days(weeks as f32 * 5.0)) {
+                            &time_in_plan) {
             Ok(Some(d)) => {
-                plan_now = d;
                 row.set_plan(d.days());
-                row.set_left(d.days() - done);
             }
             Ok(None) => {}
             Err(e) => {
@@ -61,11 +64,11 @@ impl ConfigNode {
             }
         }
 
-        let mut plan_original = Duration::new_days(0.0);
-        match self.get_plan(&ChartTime::new(&"1".to_string()).unwrap(),
-                            &Duration::new_days(weeks as f32 * 5.0)) {
+        // The work left is based on the estimate, which includes any gains.
+        match self.get_estimate(&ChartTime::new(&format!("{}", weeks + 1)).unwrap(),
+                                &time_in_plan) {
             Ok(Some(d)) => {
-                plan_original = d;
+                row.set_left(d.days() - done);
             }
             Ok(None) => {}
             Err(e) => {
@@ -73,7 +76,39 @@ impl ConfigNode {
             }
         }
 
-        row.set_gain((plan_original - plan_now).days());
+        // The gain is driven by the history of changes to the estimate.
+        let gain_trend = self.get_gain_trend(root, weeks);
+        row.set_gain(gain_trend.last().map_or(0.0, |g| g.days()));
+        row.set_gain_trend(gain_trend.iter().map(|g| g.days()).collect());
+
+        match self.get_finish() {
+            Some(finish) => row.set_finish(&finish),
+            None => {}
+        };
+
+        match self.get_slack() {
+            Some((total, free)) => {
+                row.set_slack(total.days(), free.days());
+            }
+            None => {}
+        };
+
+        // Budget rows show the plans and remaining work summed over all
+        // of the children, and the gain against the budget.
+        match self.get_budget() {
+            Ok(Some(budget)) => {
+                let (summed_plan, summed_remaining) = self.get_summed_plan(root, weeks, &start);
+                row.set_budget(budget.days());
+                row.set_plan(summed_plan.days());
+                row.set_left(summed_remaining.days());
+                row.set_gain((budget - summed_plan).days());
+                if summed_plan > budget {
+                    self.add_note(&format!("Over budget by {} days", (summed_plan - budget).days()));
+                }
+            }
+            Ok(None) => {}
+            Err(e) => self.add_note(&e),
+        };
 
         for n in self.data
                 .borrow()
@@ -89,9 +124,13 @@ impl ConfigNode {
             .map(|x| x.clone())
             .collect();
 
-        match self.get_who(&valid_who) {
-            Ok(Some(who)) => {
-                row.set_who(&who);
+        let mut contributors: Vec<String> = Vec::new();
+        match self.get_owners(&valid_who) {
+            Ok(Some(owners)) => {
+                row.set_who(&owners.to_string());
+                if owners.get_people().len() > 1 {
+                    contributors = owners.get_people().clone();
+                }
             }
             Ok(None) => {}
             Err(e) => {
@@ -100,6 +139,33 @@ impl ConfigNode {
         };
         context.add_row(row);
 
+        // Tasks with several owners show each owner's share of the work.
+        for who in contributors {
+            let contribution = match self.data.borrow().contributions.get(&who) {
+                Some(c) => c.clone(),
+                None => continue,
+            };
+            let mut row = TemplateRow::new(self.data.borrow().level + 1,
+                                           self.data.borrow().line_num,
+                                           &format!("({})", who));
+            row.set_who(&who);
+            let holidays = match root.data.borrow().holidays.get(&who) {
+                Some(h) => h.get_weekly_numbers(weeks),
+                None => vec![0.0; weeks as usize],
+            };
+            let mut count = 0;
+            for val in &contribution.get_weekly_numbers(weeks) {
+                row.add_cell(*val / 4.0,
+                             count == start_week,
+                             holidays[count as usize] > 0.0);
+                count += 1;
+            }
+            let done = contribution.count_range(0..start.get_quarter()) / 4.0;
+            row.set_done(done);
+            row.set_left(contribution.count() / 4.0 - done);
+            context.add_row(row);
+        }
+
         // Set up row data for children
         for child_rc in &self.children {
             try!(child_rc.borrow().display_gantt_internal(root, context));
@@ -118,8 +184,7 @@ impl ConfigNode {
 
         // Get start time for the period to allocate.  Assume that everything
         // prior to this has been committed.
-        let start: ChartTime = try!(self.get_config_val("today",
-                                                        Some(ChartTime::new("1").unwrap())));
+        let start: ChartTime = try!(self.get_today());
 
         //let end: ChartTime = ChartTime::new(&format!("{}", weeks+1)).unwrap();
 
@@ -128,22 +193,156 @@ impl ConfigNode {
 
         // Set up row data for people
         for (who, cells) in &self.data.borrow().people {
+            if !self.data.borrow().availability.contains_key(who) {
+                // External resources aren't shown.
+                continue;
+            }
 
+            let holidays = match self.data.borrow().holidays.get(who) {
+                Some(h) => h.get_weekly_numbers(weeks),
+                None => vec![0.0; weeks as usize],
+            };
             let mut row = TemplateRow::new(0, 0, &who);
             let mut count = 0;
             for val in &cells.get_weekly_numbers(weeks) {
-                row.add_cell(*val as f32 / 4.0, count == start_week);
+                row.add_cell(*val / 4.0,
+                             count == start_week,
+                             holidays[count as usize] > 0.0);
                 count += 1;
             }
-            row.set_left(cells.count() as f32 / 4.0);
+            row.set_left(cells.count() / 4.0);
             context.add_row(row);
         }
 
         // Set up row data for nodes
         try!(self.display_gantt_internal(self, context));
 
+        for report in self.get_deadline_summary() {
+            context.add_deadline(report.get_path(),
+                                 report.get_kind(),
+                                 &report.get_due(),
+                                 report.get_projected(),
+                                 report.get_missed().days());
+        }
+
+        match self.get_optimiser_report() {
+            Some(report) => {
+                context.add_optimiser_line(&format!("Tried {} schedules: weighted lateness \
+                                                     of {} days, against {} days for the \
+                                                     greedy allocation",
+                                                    report.get_iterations(),
+                                                    report.get_lateness(),
+                                                    report.get_greedy_lateness()));
+                for change in report.get_changes() {
+                    context.add_optimiser_line(change);
+                }
+                for &(ref path, ref kind, greedy, optimised) in report.get_deadlines() {
+                    if greedy != optimised {
+                        context.add_optimiser_line(&format!("{} {}: {} days late, against {} \
+                                                             days",
+                                                            path,
+                                                            kind,
+                                                            optimised.days(),
+                                                            greedy.days()));
+                    }
+                }
+            }
+            None => {}
+        };
+
+        match self.get_simulation_report() {
+            Some(report) => {
+                context.set_simulation(report.get_runs(), report.get_seed(), &PERCENTILES);
+                for &(ref path, ref times) in report.get_finishes() {
+                    context.add_simulation_result(path,
+                                                  "Finish",
+                                                  times.iter()
+                                                      .map(|t| format!("{}", t))
+                                                      .collect());
+                }
+                for &(ref path, budget, ref plans) in report.get_costs() {
+                    context.add_simulation_result(path,
+                                                  &format!("Plan, against a budget of {} \
+                                                            days",
+                                                           budget.days()),
+                                                  plans.iter()
+                                                      .map(|p| format!("{}", p.days()))
+                                                      .collect());
+                }
+            }
+            None => {}
+        };
+
         //Err("display_gantt is not yet implemented".to_string())
         Ok(())
 
     }
+
+    /// Set up the comparison between this chart, the baseline, and the
+    /// what-if scenarios.  A value is blank if it isn't in a scenario.
+    pub fn display_scenarios(&self,
+                             scenarios: &Vec<(String, ScenarioSummary)>,
+                             context: &mut TemplateContext) {
+
+        if scenarios.len() == 0 {
+            return;
+        }
+        let baseline = match self.get_scenario_summary() {
+            Ok(summary) => summary,
+            Err(e) => {
+                self.add_note(&e);
+                return;
+            }
+        };
+        for &(ref name, _) in scenarios {
+            context.add_scenario(name);
+        }
+
+        fn compare<T, F>(context: &mut TemplateContext,
+                         what: &str,
+                         baseline: &Vec<(String, T)>,
+                         scenarios: &Vec<(String, ScenarioSummary)>,
+                         get: F,
+                         format: &Fn(&T) -> String)
+            where F: Fn(&ScenarioSummary) -> &Vec<(String, T)>
+        {
+            for &(ref name, ref value) in baseline {
+                let mut values = vec![format(value)];
+                for &(_, ref summary) in scenarios {
+                    values.push(get(summary)
+                                    .iter()
+                                    .find(|&&(ref n, _)| n == name)
+                                    .map_or(String::new(), |&(_, ref v)| format(v)));
+                }
+                context.add_comparison(&format!("{} {}", what, name), values);
+            }
+        }
+
+        compare(context,
+                "Finish of",
+                baseline.get_finishes(),
+                scenarios,
+                |s| s.get_finishes(),
+                &|f| f.map_or(String::new(), |t| format!("{}", t)));
+        compare(context,
+                "Gain on",
+                baseline.get_gains(),
+                scenarios,
+                |s| s.get_gains(),
+                &|g| format!("{}", g.days()));
+        compare(context,
+                "Utilisation of",
+                baseline.get_utilisation(),
+                scenarios,
+                |s| s.get_utilisation(),
+                &|u| format!("{:.0}%", u));
+
+        if scenarios.iter().any(|&(_, ref s)| s.get_notes().len() > 0) {
+            let mut values = vec![String::new()];
+            for &(_, ref summary) in scenarios {
+                values.push(summary.get_notes().join("; "));
+            }
+            context.add_comparison("Notes", values);
+        }
+    }
 }
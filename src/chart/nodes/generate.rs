@@ -51,6 +51,12 @@ impl ConfigNode {
             };
         }
 
+        // Once the root has read the whole config, all of the nodes can
+        // be told when the chart starts.
+        if self.data.borrow().level == 0 {
+            try!(self.update_start_date());
+        }
+
         Ok(())
     }
 }
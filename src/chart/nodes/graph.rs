@@ -0,0 +1,399 @@
+use super::super::duration::*;
+use super::super::period::*;
+use super::super::time::*;
+use super::optimise::*;
+use super::super::SchedulingStrategy;
+use super::super::DependencyType;
+use super::*;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+
+/// A dependency of a task on another node in the chart
+#[derive(Clone)]
+pub struct TaskDependency {
+    pub node: Rc<RefCell<ConfigNode>>,
+    pub kind: DependencyType,
+    pub lag: Duration,
+}
+
+/// The leaf tasks in the chart, and the dependencies between them.
+///
+/// The tasks are ordered so that each task is allocated after all of
+/// the tasks it depends on.  Where there is a choice, tasks with a higher
+/// priority go first, then config file order is used.
+pub struct TaskGraph {
+    /// Leaf nodes, in config file order
+    tasks: Vec<Rc<RefCell<ConfigNode>>>,
+
+    /// The dependencies of each task, including those that apply to
+    /// its parents, and those implied by serial scheduling.
+    dependencies: Vec<Vec<TaskDependency>>,
+
+    /// Task indices, in the order that they should be allocated.  Tasks
+    /// in (or waiting on) a dependency cycle are omitted.
+    order: Vec<usize>,
+
+    /// The tasks that must be allocated before each task
+    predecessors: Vec<BTreeSet<usize>>,
+
+    /// The index of each task, by node
+    index: HashMap<*const RefCell<ConfigNode>, usize>,
+}
+
+impl TaskGraph {
+    /// Build the graph for all of the nodes under the root.
+    ///
+    /// Problems with the dependencies, including cycles, are added as
+    /// notes on the nodes involved.
+    pub fn new(root: &ConfigNode) -> TaskGraph {
+
+        let mut graph = TaskGraph {
+            tasks: Vec::new(),
+            dependencies: Vec::new(),
+            order: Vec::new(),
+            predecessors: Vec::new(),
+            index: HashMap::new(),
+        };
+
+        for child_rc in &root.children {
+            graph.add_node(root, child_rc, &Vec::new());
+        }
+        graph.sort();
+
+        graph
+    }
+
+    /// The task indices, in the order that they should be allocated
+    pub fn get_order(&self) -> &Vec<usize> {
+        &self.order
+    }
+
+    /// The number of tasks
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Whether one task must be allocated before another
+    pub fn depends_on(&self, ix: usize, other: usize) -> bool {
+        self.predecessors[ix].contains(&other)
+    }
+
+    /// Allocate the tasks in the order given by a schedule, holding each
+    /// back by its delay.  The order must respect the dependencies.
+    ///
+    /// This must be called once the allocation window is set up.
+    pub fn set_schedule(&mut self, schedule: &Schedule) {
+
+        self.order = schedule.get_order().clone();
+        for ix in 0..self.tasks.len() {
+            let delay = schedule.get_delay(ix);
+            if delay == 0 {
+                continue;
+            }
+            let task = self.tasks[ix].borrow();
+            let start = task.data
+                .borrow()
+                .start
+                .unwrap()
+                .get_quarter();
+            task.data.borrow_mut().update_start(ChartTime::new_from_quarter(start + delay));
+        }
+    }
+
+    /// The task at an index
+    pub fn get_task(&self, ix: usize) -> &Rc<RefCell<ConfigNode>> {
+        &self.tasks[ix]
+    }
+
+    /// The dependencies of the task at an index
+    pub fn get_dependencies(&self, ix: usize) -> &Vec<TaskDependency> {
+        &self.dependencies[ix]
+    }
+
+    /// Calculate the slack on each task, once all tasks are allocated.
+    ///
+    /// Total slack is how far a task's finish could slip without any task
+    /// missing its latest end, or the end of the chart.  Free slack is how
+    /// far it could slip without delaying anything that depends on it.
+    /// Tasks with no total slack are on the critical path.
+    pub fn calculate_slack(&self) {
+
+        let periods: Vec<Option<ChartPeriod>> = self.tasks
+            .iter()
+            .map(|t| t.borrow().get_allocation())
+            .collect();
+
+        // Work out which tasks are constrained by each task.  A start-to-
+        // start dependency on a parent node is only constrained by the
+        // first of its tasks to start.
+        let mut successors: Vec<Vec<(usize, DependencyType, i32)>> =
+            vec![Vec::new(); self.tasks.len()];
+        for ix in 0..self.tasks.len() {
+            for dep in &self.dependencies[ix] {
+                let dep_period = dep.node.borrow().get_subtree_allocation();
+                for pred in self.get_task_indices(&dep.node) {
+                    if dep.kind == DependencyType::StartToStart &&
+                       periods[pred].map(|p| p.get_first()) != dep_period.map(|p| p.get_first()) {
+                        continue;
+                    }
+                    successors[pred].push((ix, dep.kind, dep.lag.quarters()));
+                }
+            }
+        }
+
+        // Work backwards through the tasks, so that the latest finish of
+        // every successor is known before it is needed.
+        let mut latest_finish: Vec<Option<i32>> = vec![None; self.tasks.len()];
+        for ix in self.order.iter().rev() {
+            let period = match periods[*ix] {
+                Some(p) => p,
+                None => continue,
+            };
+            let first = period.get_first() as i32;
+            let last = period.get_last() as i32;
+
+            let task = self.tasks[*ix].borrow();
+            let deadline = task.data
+                .borrow()
+                .end
+                .unwrap()
+                .get_quarter() as i32;
+            let mut latest = deadline;
+            let mut free_latest = deadline;
+            for &(succ, kind, lag) in &successors[*ix] {
+                let (succ_period, succ_latest) = match (periods[succ], latest_finish[succ]) {
+                    (Some(p), Some(l)) => (p, l),
+                    _ => continue,
+                };
+                let succ_first = succ_period.get_first() as i32;
+                let succ_last = succ_period.get_last() as i32;
+                let succ_slack = succ_latest - succ_last;
+                let (limit, free_limit) = match kind {
+                    DependencyType::FinishToStart => {
+                        (succ_first + succ_slack - 1 - lag, succ_first - 1 - lag)
+                    }
+                    DependencyType::StartToStart => {
+                        (last + succ_first + succ_slack - lag - first,
+                         last + succ_first - lag - first)
+                    }
+                    DependencyType::FinishToFinish => {
+                        (succ_last + succ_slack - lag, succ_last - lag)
+                    }
+                };
+                if limit < latest {
+                    latest = limit;
+                }
+                if free_limit < free_latest {
+                    free_latest = free_limit;
+                }
+            }
+            if free_latest > latest {
+                free_latest = latest;
+            }
+            latest_finish[*ix] = Some(latest);
+
+            let mut task_data = task.data.borrow_mut();
+            task_data.total_slack = Some(Duration::new_quarters(latest - last));
+            task_data.free_slack = Some(Duration::new_quarters(free_latest - last));
+        }
+    }
+
+    /// Add a node, and its children, to the graph.
+    ///
+    /// Dependencies of the parent nodes are passed in, and apply to all
+    /// of this node's tasks.
+    fn add_node(&mut self,
+                root: &ConfigNode,
+                node_rc: &Rc<RefCell<ConfigNode>>,
+                inherited: &[TaskDependency]) {
+
+        let node = node_rc.borrow();
+        let mut deps = inherited.to_vec();
+
+        match node.get_dependencies() {
+            Ok(local_deps) => {
+                let path = node.get_path();
+                for dep in local_deps {
+                    let dep_path = dep.get_path().to_string();
+                    if dep_path == path || path.starts_with(&format!("{}/", dep_path)) ||
+                       dep_path.starts_with(&format!("{}/", path)) {
+                        node.add_note(&format!("Cannot depend on {}, as it overlaps this node",
+                                               dep_path));
+                        continue;
+                    }
+                    match root.find_node_by_path(&dep_path) {
+                        Some(dep_node) => {
+                            deps.push(TaskDependency {
+                                          node: dep_node,
+                                          kind: dep.get_kind(),
+                                          lag: dep.get_lag(),
+                                      });
+                        }
+                        None => {
+                            node.add_note(&format!("Unknown node in dependency: {}", dep_path));
+                        }
+                    };
+                }
+            }
+            Err(e) => {
+                node.add_note(&e);
+            }
+        };
+
+        if node.is_leaf() {
+            self.index.insert(&**node_rc as *const RefCell<ConfigNode>, self.tasks.len());
+            self.tasks.push(node_rc.clone());
+            self.dependencies.push(deps);
+            return;
+        }
+
+        // If the children are scheduled serially, then each child
+        // depends on all of the children before it, unless it's an
+        // external task with a start time.
+        let mut scheduling_serial = false;
+        match node.get_scheduling_strategy() {
+            Ok(SchedulingStrategy::Serial) => {
+                scheduling_serial = true;
+            }
+            Err(e) => {
+                node.add_note(&e);
+            }
+            _ => {}
+        };
+
+        for (ix, child_rc) in node.children.iter().enumerate() {
+            let mut child_deps = deps.clone();
+            if scheduling_serial && !child_rc.borrow().has_external_start(root) {
+                for sibling_rc in &node.children[..ix] {
+                    child_deps.push(TaskDependency {
+                                        node: sibling_rc.clone(),
+                                        kind: DependencyType::FinishToStart,
+                                        lag: Duration::new_quarters(0),
+                                    });
+                }
+            }
+            self.add_node(root, child_rc, &child_deps);
+        }
+    }
+
+    /// Get the indices of the tasks that make up a node
+    fn get_task_indices(&self, node_rc: &Rc<RefCell<ConfigNode>>) -> Vec<usize> {
+
+        let mut leaves = Vec::new();
+        if node_rc.borrow().is_leaf() {
+            leaves.push(node_rc.clone());
+        } else {
+            node_rc.borrow().collect_leaves(&mut leaves);
+        }
+
+        let mut indices = Vec::new();
+        for leaf in &leaves {
+            if let Some(ix) = self.index.get(&(&**leaf as *const RefCell<ConfigNode>)) {
+                indices.push(*ix);
+            }
+        }
+        indices
+    }
+
+    /// Order the tasks so that each task comes after everything it
+    /// depends on, and report any cycles.
+    fn sort(&mut self) {
+
+        // Work out which tasks must precede each task, and which tasks
+        // are waiting for each task.
+        let mut predecessors: Vec<BTreeSet<usize>> = Vec::new();
+        let mut successors: Vec<Vec<usize>> = vec![Vec::new(); self.tasks.len()];
+        for ix in 0..self.tasks.len() {
+            let mut preds = BTreeSet::new();
+            for dep in &self.dependencies[ix] {
+                for pred in self.get_task_indices(&dep.node) {
+                    if preds.insert(pred) {
+                        successors[pred].push(ix);
+                    }
+                }
+            }
+            predecessors.push(preds);
+        }
+
+        // Tasks that are ready are ordered by highest priority, then by
+        // config order.
+        let keys: Vec<(i32, usize)> = self.tasks
+            .iter()
+            .enumerate()
+            .map(|(ix, task)| {
+                let task = task.borrow();
+                match task.get_priority() {
+                    Ok(priority) => (-priority, ix),
+                    Err(e) => {
+                        task.add_note(&e);
+                        (0, ix)
+                    }
+                }
+            })
+            .collect();
+
+        // Repeatedly take the first task that has nothing left to wait for.
+        let mut waiting: Vec<BTreeSet<usize>> = predecessors.clone();
+        let mut ready: BTreeSet<(i32, usize)> = (0..self.tasks.len())
+            .filter(|ix| waiting[*ix].is_empty())
+            .map(|ix| keys[ix])
+            .collect();
+        let mut done = vec![false; self.tasks.len()];
+        self.order.clear();
+        while let Some(&key) = ready.iter().next() {
+            ready.remove(&key);
+            let ix = key.1;
+            done[ix] = true;
+            self.order.push(ix);
+            for other in &successors[ix] {
+                if !done[*other] && waiting[*other].remove(&ix) && waiting[*other].is_empty() {
+                    ready.insert(keys[*other]);
+                }
+            }
+        }
+
+        self.predecessors = predecessors.clone();
+
+        // Anything left over is in, or waiting on, a cycle.
+        let stuck: Vec<usize> = (0..self.tasks.len()).filter(|ix| !done[*ix]).collect();
+        let reachable: Vec<BTreeSet<usize>> = stuck.iter()
+            .map(|ix| TaskGraph::reachable(&predecessors, &done, *ix))
+            .collect();
+        for (pos, ix) in stuck.iter().enumerate() {
+            let cycle: Vec<String> = stuck.iter()
+                .enumerate()
+                .filter(|&(other_pos, _)| {
+                            reachable[pos].contains(&stuck[other_pos]) &&
+                            reachable[other_pos].contains(ix)
+                        })
+                .map(|(_, other)| self.tasks[*other].borrow().get_path())
+                .collect();
+            let task = self.tasks[*ix].borrow();
+            if cycle.is_empty() {
+                task.add_note("Not allocated: depends on a dependency cycle");
+            } else {
+                task.add_note(&format!("Not allocated: dependency cycle with {}",
+                                       cycle.join(", ")));
+            }
+        }
+    }
+
+    /// Get the set of unallocated tasks that can be reached by following
+    /// predecessors from a task.
+    fn reachable(predecessors: &[BTreeSet<usize>],
+                 done: &[bool],
+                 start: usize)
+                 -> BTreeSet<usize> {
+
+        let mut found = BTreeSet::new();
+        let mut to_visit = vec![start];
+        while let Some(ix) = to_visit.pop() {
+            for pred in &predecessors[ix] {
+                if !done[*pred] && found.insert(*pred) {
+                    to_visit.push(*pred);
+                }
+            }
+        }
+        found
+    }
+}
@@ -4,48 +4,137 @@ use super::super::ResourcingStrategy;
 use super::*;
 
 impl ConfigNode {
-    fn get_management_row(&self) -> Option<Weak<RefCell<ConfigNode>>> {
+    /// Get all of the management nodes, in config order.  Children of a
+    /// management node aren't searched.
+    fn get_management_rows(&self, rows: &mut Vec<Weak<RefCell<ConfigNode>>>) {
 
         match self.get_resourcing_strategy() {
             Ok(Some(ResourcingStrategy::Management)) => {
-                return self.get_weak_ref();
+                match self.get_weak_ref() {
+                    Some(node_ref) => rows.push(node_ref),
+                    None => {}
+                };
             }
             _ => {
                 for child_rc in &self.children {
-                    match child_rc.borrow().get_management_row() {
-                        Some(node_ref) => {
-                            return Some(node_ref);
-                        }
-                        None => {}
-                    };
+                    child_rc.borrow().get_management_rows(rows);
                 }
             }
         };
-        return None;
+    }
+
+    /// Get the people that a management node manages, from "manages".
+    /// This isn't inherited.
+    fn get_managees(&self) -> Option<Vec<String>> {
+
+        match self.data.borrow().attributes.get("manages") {
+            Some(manages) => {
+                Some(manages.split(',')
+                         .map(|x| x.trim().to_string())
+                         .filter(|x| x.len() != 0)
+                         .collect())
+            }
+            None => None,
+        }
+    }
+
+    /// Get the management cost for each quarter of a managee's time, from
+    /// "ratio".  This isn't inherited, and defaults to 0.2.
+    fn get_management_ratio(&self) -> Result<f32, String> {
+
+        match self.data.borrow().attributes.get("ratio") {
+            Some(ratio) => {
+                match ratio.parse::<f32>() {
+                    Ok(r) if r >= 0.0 && r.is_finite() => Ok(r),
+                    _ => Err(format!("Invalid management ratio {}", ratio)),
+                }
+            }
+            None => Ok(0.2),
+        }
     }
 
     /// Allocate management resource
+    ///
+    /// Each management node is charged to its own manager.  A node
+    /// manages the people listed in "manages", or, if there's no list,
+    /// everyone in the team that isn't listed by another management node.
+    /// Nobody can be listed by more than one node.
     pub fn allocate_management_resource(&self,
                                         weeks: u32,
                                         people_hash: &mut HashMap<String, ChartTimeRow>)
                                         -> Result<(), String> {
 
-        let node;
-        match self.get_management_row() {
-            Some(node_rc) => {
-                node = node_rc.upgrade().unwrap();
-            }
-            None => {
-                self.add_note("No management node, so no resource applied");
-                return Ok(());
-            }
-        };
-        let management_row = node.borrow();
+        let mut rows = Vec::new();
+        self.get_management_rows(&mut rows);
+        if rows.len() == 0 {
+            self.add_note("No management node, so no resource applied");
+            return Ok(());
+        }
+
+        // Get start time for the period to allocate.  Assume that everything
+        // prior to this has been committed.
+        let start: ChartTime = try!(self.get_today());
+
+        // Only people in the team are managed, for the time they are
+        // available.
+        let availability = self.data.borrow().availability.clone();
+        let team: Vec<String> = availability.keys().map(|x| x.clone()).collect();
+
+        // People that are explicitly managed by one of the nodes.  Anyone
+        // listed again is left to the first node that listed them.
+        let mut listed: Vec<(String, String)> = Vec::new();
+        let mut managees: Vec<Option<Vec<String>>> = Vec::new();
+        for row in &rows {
+            let node_rc = row.upgrade().unwrap();
+            let node = node_rc.borrow();
+            managees.push(node.get_managees().map(|list| {
+                let mut unique = Vec::new();
+                for who in list {
+                    match listed.iter().find(|l| l.0 == who) {
+                        Some(&(_, ref other)) => {
+                            node.add_note(&format!("{} is already managed by {}", who, other));
+                        }
+                        None => {
+                            listed.push((who.clone(), node.get_path()));
+                            unique.push(who);
+                        }
+                    };
+                }
+                unique
+            }));
+        }
+        let listed: Vec<String> = listed.into_iter().map(|l| l.0).collect();
+
+        for (row, managees) in rows.iter().zip(managees.into_iter()) {
+            let node_rc = row.upgrade().unwrap();
+            let management_row = node_rc.borrow();
+            management_row.allocate_manager_resource(weeks,
+                                                     &start,
+                                                     &team,
+                                                     &listed,
+                                                     managees,
+                                                     &availability,
+                                                     people_hash);
+        }
+
+        Ok(())
+    }
+
+    /// Allocate the resource for a single management node, given the
+    /// people it lists in "manages", if any.
+    fn allocate_manager_resource(&self,
+                                 weeks: u32,
+                                 start: &ChartTime,
+                                 team: &Vec<String>,
+                                 listed: &Vec<String>,
+                                 managees: Option<Vec<String>>,
+                                 availability: &HashMap<String, ChartTimeRow>,
+                                 people_hash: &mut HashMap<String, ChartTimeRow>) {
 
         let valid_who: Vec<String> = people_hash.keys().map(|x| x.clone()).collect();
         let mut manager = String::new();
         let mut err_string = String::new();
-        match management_row.get_who(&valid_who) {
+        match self.get_who(&valid_who) {
             Ok(Some(who)) => {
                 manager = who;
             }
@@ -57,68 +146,86 @@ impl ConfigNode {
             }
         }
         if err_string.len() != 0 {
-            management_row.add_note(&err_string);
-            return Ok(());
+            self.add_note(&err_string);
+            return;
         }
 
-        // Get start time for the period to allocate.  Assume that everything
-        // prior to this has been committed.
-        let start: ChartTime = try!(self.get_config_val("today",
-                                                        Some(ChartTime::new("1").unwrap())));
+        let ratio = match self.get_management_ratio() {
+            Ok(ratio) => ratio,
+            Err(e) => {
+                self.add_note(&e);
+                return;
+            }
+        };
+
+        // The manager doesn't have resource to manage themself.
+        let managees: Vec<String> = match managees {
+            Some(managees) => {
+                for who in &managees {
+                    if !team.contains(who) {
+                        self.add_note(&format!("Unknown person {} in manages", who));
+                    }
+                }
+                managees.into_iter()
+                    .filter(|x| team.contains(x) && *x != manager)
+                    .collect()
+            }
+            None => {
+                team.iter()
+                    .filter(|x| !listed.contains(x) && **x != manager)
+                    .map(|x| x.clone())
+                    .collect()
+            }
+        };
 
         // Work out management resource on a quarter-day basis,
         // add it up per week, then attempt to transfer to
         // the management row.
-        'week: for week in 1..weeks + 1 {
+        for week in 1..weeks + 1 {
             let mut weekly_resource = 0.0f32;
 
-            'quarter: for q in (week - 1) * 20..week * 20 {
+            for q in (week - 1) * 20..week * 20 {
 
                 // Don't allocate historical resource
                 if q < start.get_quarter() {
                     continue;
                 }
 
-                let mut quarterly_resource = 0.0f32;
-                'person: for (who, row) in people_hash.iter() {
-                    if *who == manager {
-                        if !row.is_set(q) {
-                            // The manager is not managing, so no
-                            // management costs this quarter.
-                            continue 'quarter;
-                        }
-                        // The manager doesn't have resource to
-                        // manage himself.
-                        continue 'person;
-                    } else {
-                        if row.is_set(q) {
-                            // Add on 20% cost for managing the managee.
-                            quarterly_resource += 0.2;
-                        }
-                    }
+                // If the manager is not managing, there are no
+                // management costs this quarter.
+                if !people_hash[&manager].is_set(q) {
+                    continue;
+                }
+
+                for who in &managees {
+                    weekly_resource += ratio * people_hash[who].get(q);
                 }
-                weekly_resource += quarterly_resource;
             }
 
             // Now transfer the weekly resource from the manager's
-            // personal row to the management row.
-            if weekly_resource > 20.0 {
-                weekly_resource = 20.0;
+            // personal row to the management row.  The manager can't
+            // spend more than the time they work that week.
+            let week_range = (week - 1) * 20..week * 20;
+            let working = match availability.get(&manager) {
+                Some(row) => row.count_range(week_range),
+                None => people_hash[&manager].count_range(week_range),
+            };
+            if weekly_resource > working {
+                weekly_resource = working;
             }
-            let mut management_data = management_row.data.borrow_mut();
+            let mut management_data = self.data.borrow_mut();
             match people_hash.get_mut(&manager)
                       .unwrap()
                       .fill_transfer_to(&mut management_data.cells,
-                                        weekly_resource.ceil() as u32,
+                                        weekly_resource,
                                         (week - 1) * 20..week * 20) {
-                (_, _, unallocated) if unallocated > 0 => {
+                (_, _, unallocated) if unallocated > 0.0 => {
                     management_data.add_note(&format!("Unable to allocate {} day(s) in week {}",
-                                                      unallocated as f32 / 4.0,
+                                                      unallocated / 4.0,
                                                       week));
                 }
                 _ => {}
             };
         }
-        Ok(())
     }
 }
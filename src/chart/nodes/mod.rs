@@ -3,16 +3,31 @@ mod allocate;
 mod display;
 mod derive;
 mod management;
+mod graph;
+mod reconcile;
+mod deadline;
+mod optimise;
+mod scenario;
+mod simulate;
 
 use std::collections::HashMap;
 use std::collections::BTreeMap;
+use std::cell::Cell;
 use std::cell::RefCell;
 use std::str::FromStr;
 use std::rc::Rc;
 use std::rc::Weak;
 use std::fmt::Display;
+use super::date::*;
+use super::duration::*;
 use super::time::*;
+use super::period::*;
 use super::timerow::*;
+use super::timesheet::*;
+
+pub use self::optimise::OptimiserReport;
+pub use self::scenario::ScenarioSummary;
+pub use self::simulate::{SimulationReport, PERCENTILES};
 
 #[derive(Debug)]
 struct ConfigNodeData {
@@ -25,16 +40,66 @@ struct ConfigNodeData {
     // People are only defined on the root node
     people: HashMap<String, ChartTimeRow>,
 
+    // The time that people are available, before any resource is
+    // allocated.  Again, only defined on the root node.
+    availability: HashMap<String, ChartTimeRow>,
+
+    // The holidays for each person.  Only defined on the root node.
+    holidays: HashMap<String, ChartTimeRow>,
+
     // Cells are only used on leaf nodes
     cells: ChartTimeRow,
 
+    // The part of the cells that each owner of a leaf node works.
+    contributions: BTreeMap<String, ChartTimeRow>,
+
+    // The person chosen to do a task that can be done by anyone with a
+    // role.
+    chosen: Option<String>,
+
+    // The share of its parent's plan given to a node without a plan of
+    // its own, when the parent's plan is split.
+    split_plan: Option<Duration>,
+
     // Optional first and last dates that the task
     // can happen on.
     start: Option<ChartTime>,
     end: Option<ChartTime>,
 
+    // The period spanned by future work on a leaf node, once
+    // it has been allocated.
+    allocation: Option<ChartPeriod>,
+
+    // The quarter that the work on a leaf node is expected to finish,
+    // including any work that didn't fit in the period.
+    projected: Option<u32>,
+
+    // How far a leaf node can slip before something misses its latest
+    // end (total), or before anything depending on it is delayed (free).
+    total_slack: Option<Duration>,
+    free_slack: Option<Duration>,
+
     // Notes are problems to display on the chart
     notes: Vec<String>,
+
+    // How the optimiser did, if it was used.  Only defined on the root
+    // node.
+    optimiser: Option<OptimiserReport>,
+
+    // The point, between 0 and 1, in the range of each of this node's
+    // estimates that is used during a simulation run.
+    sample: Option<f32>,
+
+    // The results of simulating the chart, if it was simulated.  Only
+    // defined on the root node.
+    simulation: Option<SimulationReport>,
+
+    // The work done, from a timesheet.  Only defined on the root node.
+    timesheet: Option<Timesheet>,
+
+    // Problems with the what-if scenario applied to the chart, if any.
+    // Only defined on the root node.
+    scenario_notes: Vec<String>,
 }
 
 impl ConfigNodeData {
@@ -46,10 +111,24 @@ impl ConfigNodeData {
             level: level,
             attributes: HashMap::new(),
             people: HashMap::new(),
+            availability: HashMap::new(),
+            holidays: HashMap::new(),
             cells: ChartTimeRow::new(),
+            contributions: BTreeMap::new(),
+            chosen: None,
+            split_plan: None,
             start: None,
             end: None,
+            allocation: None,
+            projected: None,
+            total_slack: None,
+            free_slack: None,
             notes: Vec::new(),
+            optimiser: None,
+            sample: None,
+            simulation: None,
+            timesheet: None,
+            scenario_notes: Vec::new(),
         }
 
     }
@@ -96,6 +175,11 @@ pub struct ConfigNode {
 
     parent: Option<Weak<RefCell<ConfigNode>>>,
 
+    // The date of the Monday of week 1, if the chart has a start date.
+    // This is shared by all of the nodes in a chart, so that any of them
+    // can read dates, and is updated by the root when its config changes.
+    start_date: Rc<Cell<Option<ChartDate>>>,
+
     data: RefCell<ConfigNodeData>,
 }
 
@@ -105,6 +189,7 @@ impl ConfigNode {
             data: RefCell::new(ConfigNodeData::new(name, level, indent, line_num)),
             children: Vec::new(),
             parent: None,
+            start_date: Rc::new(Cell::new(None)),
         }
     }
 
@@ -116,10 +201,24 @@ impl ConfigNode {
     }
 
     fn new_child(&mut self, name: &str, indent: u32, line_num: u32) {
-        self.children.push(Rc::new(RefCell::new(ConfigNode::new(name,
-                                                                self.data.borrow().level + 1,
-                                                                indent,
-                                                                line_num))));
+        let mut child = ConfigNode::new(name, self.data.borrow().level + 1, indent, line_num);
+        child.start_date = self.start_date.clone();
+        self.children.push(Rc::new(RefCell::new(child)));
+    }
+
+    /// Parse a time in this chart, which can be a date if the chart has
+    /// a start date.
+    pub fn new_time(&self, desc: &str) -> Result<ChartTime, String> {
+        ChartTime::new_with_start(desc, self.start_date.get())
+    }
+
+    /// Whether this is one of the "special" nodes, such as [people], that
+    /// configure the chart rather than being part of it.
+    fn is_special(&self) -> bool {
+        let name = &self.data.borrow().name;
+        ["chart", "people", "external", "holidays", "rows", "scenarios"]
+            .iter()
+            .any(|s| *name == format!("[{}]", s))
     }
 
     pub fn count_nodes(&self) -> u32 {
@@ -158,6 +257,99 @@ impl ConfigNode {
         None
     }
 
+    /// Find a node from its path, ie the names of the nodes from the
+    /// top level down, separated by '/'.
+    ///
+    /// Node names can themselves contain '/'.
+    pub fn find_node_by_path(&self, path: &str) -> Option<Rc<RefCell<ConfigNode>>> {
+
+        for child_rc in &self.children {
+            let child_node = child_rc.borrow();
+            let name = child_node.data.borrow().name.clone();
+            if path == name {
+                return Some(child_rc.clone());
+            }
+            if path.starts_with(&format!("{}/", name)) {
+                match child_node.find_node_by_path(&path[name.len() + 1..]) {
+                    Some(node) => return Some(node),
+                    None => {}
+                };
+            }
+        }
+
+        None
+    }
+
+    /// Get the path to this node, in the form used by find_node_by_path
+    pub fn get_path(&self) -> String {
+
+        let name = self.data.borrow().name.clone();
+        if self.data.borrow().level <= 1 {
+            return name;
+        }
+
+        match self.parent {
+            Some(ref p) => {
+                match p.upgrade() {
+                    Some(parent) => format!("{}/{}", parent.borrow().get_path(), name),
+                    None => name,
+                }
+            }
+            None => name,
+        }
+    }
+
+    /// Add all leaf nodes below this one to a vector, in config order
+    pub fn collect_leaves(&self, leaves: &mut Vec<Rc<RefCell<ConfigNode>>>) {
+
+        for child_rc in &self.children {
+            if child_rc.borrow().is_leaf() {
+                leaves.push(child_rc.clone());
+            } else {
+                child_rc.borrow().collect_leaves(leaves);
+            }
+        }
+    }
+
+    /// Get the period spanned by future work on this node and all of
+    /// its children.
+    pub fn get_subtree_allocation(&self) -> Option<ChartPeriod> {
+
+        let mut period = self.data.borrow().allocation;
+        for child_rc in &self.children {
+            period = match (period, child_rc.borrow().get_subtree_allocation()) {
+                (Some(a), Some(b)) => Some(a.span(&b)),
+                (a, None) => a,
+                (None, b) => b,
+            };
+        }
+
+        period
+    }
+
+    /// Get the total and free slack for this node.
+    ///
+    /// For a parent node, this is the smallest slack of any of its children.
+    pub fn get_slack(&self) -> Option<(Duration, Duration)> {
+
+        let mut slack = match (self.data.borrow().total_slack, self.data.borrow().free_slack) {
+            (Some(total), Some(free)) => Some((total, free)),
+            _ => None,
+        };
+        for child_rc in &self.children {
+            slack = match (slack, child_rc.borrow().get_slack()) {
+                (Some((total_a, free_a)), Some((total_b, free_b))) => {
+                    Some((if total_a < total_b { total_a } else { total_b },
+                          if free_a < free_b { free_a } else { free_b }))
+                }
+                (a, None) => a,
+                (None, b) => b,
+            };
+        }
+
+        slack
+    }
+
     pub fn get_weak_ref(&self) -> Option<Weak<RefCell<ConfigNode>>> {
 
         match self.parent {
@@ -179,6 +371,69 @@ impl ConfigNode {
     }
 
 
+    /// Get the period spanned by future work allocated to this node
+    pub fn get_allocation(&self) -> Option<ChartPeriod> {
+        self.data.borrow().allocation
+    }
+
+    /// Clear everything that fill_in_gantt works out, for this node and
+    /// all children, so that the chart can be allocated again.
+    fn reset_allocation(&self) {
+        {
+            let mut data = self.data.borrow_mut();
+            data.people.clear();
+            data.availability.clear();
+            data.holidays.clear();
+            data.cells = ChartTimeRow::new();
+            data.contributions.clear();
+            data.chosen = None;
+            data.split_plan = None;
+            data.start = None;
+            data.end = None;
+            data.allocation = None;
+            data.projected = None;
+            data.total_slack = None;
+            data.free_slack = None;
+            data.notes.clear();
+        }
+
+        for child_rc in &self.children {
+            child_rc.borrow().reset_allocation();
+        }
+    }
+
+    /// Get the part of the cells of this node that a person works
+    pub fn get_contribution(&self, who: &str) -> Option<ChartTimeRow> {
+        self.data.borrow().contributions.get(who).map(|c| c.clone())
+    }
+
+    /// Get the report from the optimiser, if it was used.
+    ///
+    /// This must only be called on the root node.
+    pub fn get_optimiser_report(&self) -> Option<OptimiserReport> {
+        self.data.borrow().optimiser.clone()
+    }
+
+    /// Set the timesheet of work done, which is transferred to the nodes
+    /// along with their commitments.
+    ///
+    /// This must only be called on the root node.
+    pub fn set_timesheet(&self, timesheet: Timesheet) {
+        self.data.borrow_mut().timesheet = Some(timesheet);
+    }
+
+    /// Get the results of simulating the chart, if it was simulated.
+    ///
+    /// This must only be called on the root node.
+    pub fn get_simulation_report(&self) -> Option<SimulationReport> {
+        self.data.borrow().simulation.clone()
+    }
+
+    /// Get the notes that have been added to this node
+    pub fn get_notes(&self) -> Vec<String> {
+        self.data.borrow().notes.clone()
+    }
+
     /// Return true if this is a leaf node
     pub fn is_leaf(&self) -> bool {
         self.children.len() == 0
@@ -228,19 +483,6 @@ impl ConfigNode {
         }
     }
 
-    fn max_time_ct(&self, a: Option<ChartTime>, b: Option<ChartTime>) -> Option<ChartTime> {
-
-        match b {
-            Some(b_ct) => {
-                match a {
-                    Some(a_ct) => if b_ct > a_ct { Some(b_ct) } else { Some(a_ct) },
-                    None => Some(b_ct),
-                }
-            }
-            None => a,
-        }
-    }
-
     /// Add a note to be displayed alongside this cell
     fn add_note(&self, note: &str) {
 
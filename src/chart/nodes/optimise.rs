@@ -0,0 +1,200 @@
+use super::super::duration::*;
+use super::graph::*;
+use super::*;
+
+/// The order that tasks are allocated in, and how long each task is held
+/// back from the start of the period it can be worked in, in quarters.
+///
+/// Tasks are identified by their index in the task graph.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Schedule {
+    order: Vec<usize>,
+    delays: Vec<u32>,
+}
+
+impl Schedule {
+    /// The greedy schedule for a task graph, which uses its order and
+    /// holds nothing back.
+    pub fn new(graph: &TaskGraph) -> Schedule {
+        Schedule {
+            order: graph.get_order().clone(),
+            delays: vec![0; graph.len()],
+        }
+    }
+
+    /// The task indices, in the order that they should be allocated
+    pub fn get_order(&self) -> &Vec<usize> {
+        &self.order
+    }
+
+    /// How long the task at an index is held back, in quarters
+    pub fn get_delay(&self, ix: usize) -> u32 {
+        self.delays[ix]
+    }
+
+    /// Get the schedules that differ from this one by a single change.
+    ///
+    /// These are, in order, swapping each pair of adjacent tasks that
+    /// don't depend on each other, then holding each task back by a
+    /// further day, up to the limit.
+    fn get_neighbours(&self, graph: &TaskGraph, limit: u32) -> Vec<Schedule> {
+
+        let mut neighbours = Vec::new();
+        for pos in 1..self.order.len() {
+            if graph.depends_on(self.order[pos], self.order[pos - 1]) {
+                continue;
+            }
+            let mut schedule = self.clone();
+            schedule.order.swap(pos - 1, pos);
+            neighbours.push(schedule);
+        }
+        for ix in &self.order {
+            if self.delays[*ix] + 4 > limit {
+                continue;
+            }
+            let mut schedule = self.clone();
+            schedule.delays[*ix] += 4;
+            neighbours.push(schedule);
+        }
+
+        neighbours
+    }
+
+    /// Describe how this schedule differs from another
+    fn describe_changes(&self, other: &Schedule, graph: &TaskGraph) -> Vec<String> {
+
+        let path = |ix: usize| graph.get_task(ix).borrow().get_path();
+        let mut other_pos = vec![0; other.delays.len()];
+        for (pos, ix) in other.order.iter().enumerate() {
+            other_pos[*ix] = pos;
+        }
+
+        let mut changes = Vec::new();
+        for (pos, ix) in self.order.iter().enumerate() {
+            for later in &self.order[pos + 1..] {
+                if other_pos[*ix] > other_pos[*later] {
+                    changes.push(format!("Allocated {} before {}", path(*ix), path(*later)));
+                }
+            }
+        }
+        for ix in &self.order {
+            if self.delays[*ix] > other.delays[*ix] {
+                changes.push(format!("Held back {} by {} days",
+                                     path(*ix),
+                                     Duration::new_quarters((self.delays[*ix] -
+                                                             other.delays[*ix]) as
+                                                            i32)
+                                         .days()));
+            }
+        }
+
+        changes
+    }
+}
+
+/// How the optimiser's allocation compares with the greedy one
+#[derive(Debug, PartialEq, Clone)]
+pub struct OptimiserReport {
+    iterations: u32,
+    greedy_lateness: f32,
+    lateness: f32,
+    changes: Vec<String>,
+    deadlines: Vec<(String, String, Duration, Duration)>,
+}
+
+impl OptimiserReport {
+    /// The number of schedules that were tried, besides the greedy one
+    pub fn get_iterations(&self) -> u32 {
+        self.iterations
+    }
+
+    /// The weighted lateness of the greedy allocation, in days
+    pub fn get_greedy_lateness(&self) -> f32 {
+        self.greedy_lateness
+    }
+
+    /// The weighted lateness of the optimised allocation, in days
+    pub fn get_lateness(&self) -> f32 {
+        self.lateness
+    }
+
+    /// The changes made to the greedy schedule
+    pub fn get_changes(&self) -> &Vec<String> {
+        &self.changes
+    }
+
+    /// The path and kind of each deadline, with the days that it is missed
+    /// by in the greedy and optimised allocations.
+    pub fn get_deadlines(&self) -> &Vec<(String, String, Duration, Duration)> {
+        &self.deadlines
+    }
+}
+
+impl ConfigNode {
+    /// Allocate the chart, searching for the schedule that misses the
+    /// deadlines by the least weighted lateness.
+    ///
+    /// The search starts from the greedy schedule, and repeatedly moves to
+    /// the first neighbouring schedule that is better, until none is, or
+    /// until the given number of iterations have been tried.  Each
+    /// iteration allocates the whole chart again, so the search is
+    /// deterministic.
+    ///
+    /// This is only called on the root node.
+    pub fn optimise(&self, max_iterations: u32) -> Result<(), String> {
+
+        let weeks: u32 = try!(self.get_config_val("weeks", None));
+        let graph = try!(self.allocate_schedule(None));
+        let greedy = Schedule::new(&graph);
+        let greedy_lateness = self.get_weighted_lateness();
+        let greedy_deadlines = self.get_deadline_summary();
+
+        let mut best = greedy.clone();
+        let mut best_lateness = greedy_lateness;
+        let mut iterations = 0;
+        let mut improved = true;
+        while improved && best_lateness > 0.0 && iterations < max_iterations {
+            improved = false;
+            for candidate in best.get_neighbours(&graph, weeks * 20) {
+                if iterations == max_iterations {
+                    break;
+                }
+                iterations += 1;
+
+                self.reset_allocation();
+                try!(self.allocate_schedule(Some(&candidate)));
+                let lateness = self.get_weighted_lateness();
+                if lateness < best_lateness - 0.01 {
+                    best = candidate;
+                    best_lateness = lateness;
+                    improved = true;
+                    break;
+                }
+            }
+        }
+
+        // Leave the chart allocated with the best schedule.
+        self.reset_allocation();
+        try!(self.allocate_schedule(Some(&best)));
+
+        let deadlines = greedy_deadlines.iter()
+            .zip(self.get_deadline_summary().iter())
+            .map(|(g, o)| {
+                     (g.get_path().to_string(),
+                      g.get_kind().to_string(),
+                      g.get_missed(),
+                      o.get_missed())
+                 })
+            .collect();
+        self.data.borrow_mut().optimiser = Some(OptimiserReport {
+                                                    iterations: iterations,
+                                                    greedy_lateness: greedy_lateness,
+                                                    lateness: best_lateness,
+                                                    changes: best.describe_changes(&greedy,
+                                                                                   &graph),
+                                                    deadlines: deadlines,
+                                                });
+
+        Ok(())
+    }
+}
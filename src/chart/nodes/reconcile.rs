@@ -0,0 +1,101 @@
+use super::super::duration::*;
+use super::super::time::*;
+use super::*;
+
+impl ConfigNode {
+    /// Check that the plans on parent nodes match the plans of their
+    /// children, for this node and all of its children.
+    ///
+    /// If split-plan is set, any plan left over on a parent after its
+    /// children's plans are taken out is shared evenly between the
+    /// children that don't have a plan of their own.  Any mismatch that
+    /// remains is added as a note.
+    pub fn reconcile_plans(&self, root: &ConfigNode, weeks: u32) {
+
+        if !self.is_leaf() && self.data.borrow().level > 0 {
+            let when = ChartTime::new(&format!("{}", weeks + 1)).unwrap();
+            let today = ChartTime::new("1").unwrap();
+            let time_in_plan =
+                Duration::new_quarters(self.get_owner_availability(root, weeks)
+                    .count()
+                    .round() as i32);
+            match self.get_estimate(&when, &time_in_plan) {
+                Ok(Some(plan)) => {
+                    match self.get_split_plan() {
+                        Ok(true) => self.split_plan(root, weeks, plan),
+                        Ok(false) => {}
+                        Err(e) => self.add_note(&e),
+                    };
+
+                    let (summed_plan, _) = self.get_summed_plan(root, weeks, &today);
+                    if summed_plan != plan {
+                        self.add_note(&format!("Plan of {} days does not match the {} days \
+                                                planned for the children",
+                                               plan.days(),
+                                               summed_plan.days()));
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => self.add_note(&e),
+            };
+        }
+
+        for child_rc in &self.children {
+            child_rc.borrow().reconcile_plans(root, weeks);
+        }
+    }
+
+    /// Share out the plan left over after the children's plans are taken
+    /// out, between the children that don't have a plan.
+    ///
+    /// The shares are only kept until the allocation is reset, so that
+    /// they follow the parent's plan each time the chart is allocated.
+    fn split_plan(&self, root: &ConfigNode, weeks: u32, plan: Duration) {
+
+        let when = ChartTime::new(&format!("{}", weeks + 1)).unwrap();
+        let today = ChartTime::new("1").unwrap();
+
+        // Find the children without a plan, and the plan left over.
+        let mut leftover = plan;
+        let mut unplanned = Vec::new();
+        for child_rc in &self.children {
+            let child = child_rc.borrow();
+            let (child_plan, _) = child.get_summed_plan(root, weeks, &today);
+            let time_in_plan =
+                Duration::new_quarters(child.get_owner_availability(root, weeks)
+                    .count()
+                    .round() as i32);
+            match child.get_plan(&when, &time_in_plan) {
+                Ok(None) if child_plan.is_zero() => {
+                    unplanned.push(child_rc.clone());
+                }
+                _ => {
+                    leftover = leftover - child_plan;
+                }
+            };
+        }
+
+        if unplanned.len() == 0 {
+            return;
+        }
+        if leftover.is_negative() || leftover.is_zero() {
+            self.add_note(&format!("No plan left to split between {} children",
+                                   unplanned.len()));
+            return;
+        }
+
+        // Split the leftover evenly, giving any odd quarters to the
+        // first children.
+        let share = leftover.quarters() / unplanned.len() as i32;
+        let odd_quarters = leftover.quarters() % unplanned.len() as i32;
+        for (ix, child_rc) in unplanned.iter().enumerate() {
+            let quarters = if (ix as i32) < odd_quarters {
+                share + 1
+            } else {
+                share
+            };
+            child_rc.borrow().data.borrow_mut().split_plan =
+                Some(Duration::new_quarters(quarters));
+        }
+    }
+}
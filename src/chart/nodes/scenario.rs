@@ -0,0 +1,164 @@
+use super::super::duration::*;
+use super::super::file::*;
+use super::super::time::*;
+use super::*;
+
+/// The figures that are compared between the baseline chart and each
+/// what-if scenario.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ScenarioSummary {
+    finishes: Vec<(String, Option<ChartTime>)>,
+    gains: Vec<(String, Duration)>,
+    utilisation: Vec<(String, f32)>,
+    notes: Vec<String>,
+}
+
+impl ScenarioSummary {
+    /// The time that each top-level node is expected to finish
+    pub fn get_finishes(&self) -> &Vec<(String, Option<ChartTime>)> {
+        &self.finishes
+    }
+
+    /// The gain on each top-level node.  This is the gain against the
+    /// budget, for nodes with a budget.
+    pub fn get_gains(&self) -> &Vec<(String, Duration)> {
+        &self.gains
+    }
+
+    /// The percentage of each person's time, from today, that is
+    /// allocated.
+    pub fn get_utilisation(&self) -> &Vec<(String, f32)> {
+        &self.utilisation
+    }
+
+    /// Problems with the scenario, such as attributes that it adds
+    /// rather than replaces.
+    pub fn get_notes(&self) -> &Vec<String> {
+        &self.notes
+    }
+}
+
+impl ConfigNode {
+    /// Get the name and file of each what-if scenario, sorted by name.
+    pub fn get_scenarios(&self) -> Vec<(String, String)> {
+
+        let mut scenarios: Vec<(String, String)> =
+            match self.find_child_with_name("[scenarios]").and_then(|n| n.upgrade()) {
+                Some(node_rc) => {
+                    node_rc.borrow()
+                        .data
+                        .borrow()
+                        .attributes
+                        .iter()
+                        .map(|(name, file)| (name.clone(), file.clone()))
+                        .collect()
+                }
+                None => Vec::new(),
+            };
+        scenarios.sort();
+
+        scenarios
+    }
+
+    /// Override attributes using a scenario file.
+    ///
+    /// Each node line in the scenario is the path to a node in the chart,
+    /// eg "EAS Prod SFRs/rsl" or "[people]", and the attributes that
+    /// follow it replace or add to the attributes of that node.  Added
+    /// attributes are noted, in case the key is a typo.
+    ///
+    /// This must be called on the root node, before fill_in_gantt.
+    pub fn apply_scenario(&self, file: &mut ConfigLines) -> Result<(), String> {
+
+        let mut node_rc: Option<Rc<RefCell<ConfigNode>>> = None;
+        loop {
+            match file.get_line() {
+                Some(Line::Node(LineNode { line_num, name, .. })) => {
+                    node_rc = self.find_node_by_path(&name);
+                    if node_rc.is_none() {
+                        return Err(format!("Unknown node {} at line {} of scenario",
+                                           name,
+                                           line_num));
+                    }
+                }
+                Some(Line::Attribute(LineAttribute { key, value })) => {
+                    match node_rc {
+                        Some(ref node_rc) => {
+                            let node = node_rc.borrow();
+                            if !node.data.borrow().attributes.contains_key(&key) {
+                                self.data
+                                    .borrow_mut()
+                                    .scenario_notes
+                                    .push(format!("Scenario adds {} to {}, which doesn't have it",
+                                                  key,
+                                                  node.get_path()));
+                            }
+                            node.create_attribute(&key, &value);
+                        }
+                        None => {
+                            return Err(format!("Attribute {} isn't under a node in scenario",
+                                               key))
+                        }
+                    };
+                }
+                None => break,
+            };
+        }
+
+        // The scenario may have moved the start of the chart.
+        self.update_start_date()
+    }
+
+    /// Summarise the chart, for comparing with other scenarios.
+    ///
+    /// This can only be used on the root node, once fill_in_gantt has
+    /// allocated the work.
+    pub fn get_scenario_summary(&self) -> Result<ScenarioSummary, String> {
+
+        let weeks: u32 = try!(self.get_config_val("weeks", None));
+        let today: ChartTime = try!(self.get_today());
+
+        let mut finishes = Vec::new();
+        let mut gains = Vec::new();
+        for child_rc in &self.children {
+            let child = child_rc.borrow();
+            if child.is_special() {
+                continue;
+            }
+            let name = child.data.borrow().name.clone();
+            finishes.push((name.clone(), child.get_finish()));
+            let gain = match child.get_budget() {
+                Ok(Some(budget)) => budget - child.get_summed_plan(self, weeks, &today).0,
+                _ => {
+                    child.get_gain_trend(self, weeks)
+                        .last()
+                        .map_or(Duration::new_quarters(0), |g| *g)
+                }
+            };
+            gains.push((name, gain));
+        }
+
+        let data = self.data.borrow();
+        let mut team: Vec<&String> = data.availability.keys().collect();
+        team.sort();
+        let mut utilisation = Vec::new();
+        for who in team {
+            let available = data.availability[who].count_range(today.get_quarter()..weeks * 20);
+            if available <= 0.0 {
+                continue;
+            }
+            let left = match data.people.get(who) {
+                Some(row) => row.count_range(today.get_quarter()..weeks * 20),
+                None => available,
+            };
+            utilisation.push((who.clone(), 100.0 * (available - left) / available));
+        }
+
+        Ok(ScenarioSummary {
+               finishes: finishes,
+               gains: gains,
+               utilisation: utilisation,
+               notes: data.scenario_notes.clone(),
+           })
+    }
+}
@@ -0,0 +1,152 @@
+use super::super::duration::*;
+use super::super::random::*;
+use super::super::time::*;
+use super::*;
+
+/// The percentiles that simulations report
+pub const PERCENTILES: [u32; 3] = [50, 80, 95];
+
+/// The spread of outcomes from simulating the chart
+#[derive(Debug, PartialEq, Clone)]
+pub struct SimulationReport {
+    runs: u32,
+    seed: u64,
+    finishes: Vec<(String, Vec<ChartTime>)>,
+    costs: Vec<(String, Duration, Vec<Duration>)>,
+}
+
+impl SimulationReport {
+    /// The number of times that the chart was allocated
+    pub fn get_runs(&self) -> u32 {
+        self.runs
+    }
+
+    /// The seed for the random numbers
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The path of each node with any work, and the times that it
+    /// finished by in each of the PERCENTILES of runs.
+    pub fn get_finishes(&self) -> &Vec<(String, Vec<ChartTime>)> {
+        &self.finishes
+    }
+
+    /// The path and budget of each node with a budget, and the total of
+    /// the plans that it came within in each of the PERCENTILES of runs.
+    pub fn get_costs(&self) -> &Vec<(String, Duration, Vec<Duration>)> {
+        &self.costs
+    }
+}
+
+/// The outcome for a node in a single run: its path, the quarter that it
+/// finished in, and its budget and summed plan.
+type Outcome = (String, Option<u32>, Option<(Duration, Duration)>);
+
+/// Get the values that the given percentiles of a sorted list are within
+fn get_percentiles<T: Copy>(sorted: &[T]) -> Vec<T> {
+    PERCENTILES.iter()
+        .map(|pc| sorted[(*pc as usize * sorted.len() - 1) / 100])
+        .collect()
+}
+
+impl ConfigNode {
+    /// Choose the sample point for this node, and all children, for a run
+    fn draw_samples(&self, random: &mut Random) {
+        self.data.borrow_mut().sample = Some(random.next_fraction());
+        for child_rc in &self.children {
+            child_rc.borrow().draw_samples(random);
+        }
+    }
+
+    /// Go back to using the expected value of each estimate
+    fn clear_samples(&self) {
+        self.data.borrow_mut().sample = None;
+        for child_rc in &self.children {
+            child_rc.borrow().clear_samples();
+        }
+    }
+
+    /// Get the outcome of a run for this node, and all children, in config
+    /// order.
+    fn get_outcomes(&self,
+                    root: &ConfigNode,
+                    weeks: u32,
+                    today: &ChartTime,
+                    outcomes: &mut Vec<Outcome>) {
+
+        if self.is_special() {
+            return;
+        }
+        if self.data.borrow().level > 0 {
+            let finish = self.get_finish().map(|ct| ct.get_quarter());
+            let cost = self.get_budget()
+                .unwrap_or(None)
+                .map(|budget| (budget, self.get_summed_plan(root, weeks, today).0));
+            outcomes.push((self.get_path(), finish, cost));
+        }
+        for child_rc in &self.children {
+            child_rc.borrow().get_outcomes(root, weeks, today, outcomes);
+        }
+    }
+
+    /// Allocate the chart a number of times, each time with every plan
+    /// drawn at random from its estimate, and report the spread of
+    /// finish times and costs.
+    ///
+    /// Each run uses the greedy allocation.  The chart is left
+    /// unallocated.  This is only called on the root node.
+    pub fn simulate(&self, runs: u32, seed: u64) -> Result<(), String> {
+
+        let weeks: u32 = try!(self.get_config_val("weeks", None));
+        let today = try!(self.get_today());
+
+        let mut random = Random::new(seed);
+        let mut results: Vec<Vec<Outcome>> = Vec::new();
+        for _ in 0..runs {
+            self.draw_samples(&mut random);
+            self.reset_allocation();
+            try!(self.allocate_schedule(None));
+
+            let mut outcomes = Vec::new();
+            self.get_outcomes(self, weeks, &today, &mut outcomes);
+            results.push(outcomes);
+        }
+        self.clear_samples();
+        self.reset_allocation();
+
+        // Every run has the same nodes, in the same order.
+        let mut finishes = Vec::new();
+        let mut costs = Vec::new();
+        for ix in 0..results[0].len() {
+            let path = results[0][ix].0.clone();
+
+            let mut times: Vec<u32> = results.iter().filter_map(|r| r[ix].1).collect();
+            if !times.is_empty() {
+                times.sort();
+                finishes.push((path.clone(),
+                               get_percentiles(&times)
+                                   .iter()
+                                   .map(|q| ChartTime::new_from_quarter(*q))
+                                   .collect()));
+            }
+
+            if let Some((budget, _)) = results[0][ix].2 {
+                let mut plans: Vec<Duration> = results.iter()
+                    .filter_map(|r| r[ix].2.map(|c| c.1))
+                    .collect();
+                plans.sort();
+                costs.push((path, budget, get_percentiles(&plans)));
+            }
+        }
+
+        self.data.borrow_mut().simulation = Some(SimulationReport {
+                                                     runs: runs,
+                                                     seed: seed,
+                                                     finishes: finishes,
+                                                     costs: costs,
+                                                 });
+
+        Ok(())
+    }
+}
@@ -93,6 +93,24 @@ impl ChartPeriod {
 
     }
 
+    /// The smallest period that includes both periods
+    ///
+    /// Unlike union, this works for periods that do not overlap.
+    pub fn span(&self, other: &ChartPeriod) -> ChartPeriod {
+        ChartPeriod {
+            first: if self.first < other.first {
+                self.first
+            } else {
+                other.first
+            },
+            last: if self.last > other.last {
+                self.last
+            } else {
+                other.last
+            },
+        }
+    }
+
     pub fn get_first(&self) -> u32 {
         self.first
     }
@@ -0,0 +1,199 @@
+use super::date::*;
+use super::timerow::*;
+use std::str::FromStr;
+
+/// The config for a person, from the [people] node
+///
+/// This takes the form
+/// `<ranges>[ region=<region>][ pattern=<pattern>][ capacity=<capacity>]
+/// [ roles=<roles>]`, for example
+/// `1..4, 6..8.3, !3.2 region=uk pattern=mon-thu roles=backend,dba`.
+/// - The ranges are separated by ", ".  The person is available for the
+///   union of the ranges, less any ranges prefixed with '!'.  A range can
+///   be a single time, eg "!3.2".
+/// - The region selects the holidays that apply to the person.
+/// - The pattern is the part of each week that the person works.  It is
+///   a list of days or day ranges separated by ',', each optionally
+///   limited to mornings (":am") or afternoons (":pm"), eg
+///   `mon-wed,thu:am`.  By default, people work every day.
+/// - The capacity is the amount of work the person does in each quarter
+///   that they work, eg 0.5 for half-time, or 1.2 for overtime.  The
+///   default is 1.
+/// - The roles are the kinds of work that the person can do, separated by
+///   ','.  Tasks can be given to any person with a role (see Who).
+#[derive(Debug, PartialEq, Clone)]
+pub struct PersonConfig {
+    ranges: Vec<String>,
+    exclusions: Vec<String>,
+    region: Option<String>,
+    pattern: Option<String>,
+    capacity: f32,
+    roles: Vec<String>,
+}
+
+impl FromStr for PersonConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PersonConfig::new(s)
+    }
+}
+
+impl PersonConfig {
+    pub fn new(desc: &str) -> Result<PersonConfig, String> {
+
+        let mut config = PersonConfig {
+            ranges: Vec::new(),
+            exclusions: Vec::new(),
+            region: None,
+            pattern: None,
+            capacity: 1.0,
+            roles: Vec::new(),
+        };
+
+        for part in desc.split_whitespace() {
+            let v: Vec<&str> = part.splitn(2, '=').collect();
+            if v.len() == 2 {
+                match v[0] {
+                    "region" => config.region = Some(v[1].to_string()),
+                    "pattern" => {
+                        try!(PersonConfig::get_pattern_quarters(v[1]));
+                        config.pattern = Some(v[1].to_string());
+                    }
+                    "capacity" => {
+                        config.capacity = try!(v[1].parse::<f32>()
+                            .map_err(|e| format!("Invalid capacity {}: {}", v[1], e)));
+                        if config.capacity < 0.0 {
+                            return Err(format!("Capacity {} must not be negative", v[1]));
+                        }
+                    }
+                    "roles" => {
+                        config.roles = v[1]
+                            .split(',')
+                            .filter(|r| r.len() > 0)
+                            .map(|r| r.to_string())
+                            .collect();
+                    }
+                    _ => return Err(format!("Unrecognised option {}", part)),
+                };
+                continue;
+            }
+
+            for range in part.split(',').filter(|r| r.len() > 0) {
+                let (list, range) = if range.starts_with('!') {
+                    (&mut config.exclusions, &range[1..])
+                } else {
+                    (&mut config.ranges, range)
+                };
+
+                // A single time is a range on its own.
+                if range.contains("..") {
+                    list.push(range.to_string());
+                } else {
+                    list.push(format!("{}..{}", range, range));
+                }
+            }
+        }
+
+        if config.ranges.len() == 0 {
+            return Err(format!("No range in {}", desc));
+        }
+
+        Ok(config)
+    }
+
+    /// The region that the person is in, if any
+    pub fn get_region(&self) -> Option<String> {
+        self.region.clone()
+    }
+
+    /// Whether the person can take on work for a role
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+
+    /// Get the time that the person is available, before holidays.  The
+    /// ranges can be given as dates if the chart has a start date.
+    pub fn get_availability(&self,
+                            weeks: u32,
+                            start: Option<ChartDate>)
+                            -> Result<ChartTimeRow, String> {
+
+        let mut row = ChartTimeRow::new();
+        for range in &self.ranges {
+            row.set_row(&try!(ChartTimeRow::new_populate_range(range, weeks, start)));
+        }
+        for range in &self.exclusions {
+            row.unset_row(&try!(ChartTimeRow::new_populate_range(range, weeks, start)));
+        }
+
+        match self.pattern {
+            Some(ref pattern) => {
+                let quarters = try!(PersonConfig::get_pattern_quarters(pattern));
+                let mut working = ChartTimeRow::new();
+                for week in 0..weeks {
+                    for q in &quarters {
+                        working.set_range(week * 20 + q..week * 20 + q + 1);
+                    }
+                }
+                row.retain_row(&working);
+            }
+            None => {}
+        };
+        row.scale(self.capacity);
+
+        Ok(row)
+    }
+
+    /// Get the quarters within a week that a pattern covers
+    fn get_pattern_quarters(pattern: &str) -> Result<Vec<u32>, String> {
+
+        let mut quarters = Vec::new();
+        for part in pattern.split(',') {
+            let (days, half) = match part.find(':') {
+                Some(pos) => (&part[..pos], Some(&part[pos + 1..])),
+                None => (part, None),
+            };
+            let (first_q, last_q) = match half {
+                None => (0, 3),
+                Some("am") => (0, 1),
+                Some("pm") => (2, 3),
+                Some(other) => {
+                    return Err(format!("Invalid part of day {} in pattern {}", other, pattern))
+                }
+            };
+
+            let v: Vec<&str> = days.split('-').collect();
+            if v.len() > 2 {
+                return Err(format!("Too many days in {} in pattern {}", days, pattern));
+            }
+            let first = try!(PersonConfig::get_day(v[0], pattern));
+            let last = try!(PersonConfig::get_day(v[v.len() - 1], pattern));
+            if first > last {
+                return Err(format!("Days {} are out of order in pattern {}", days, pattern));
+            }
+
+            for day in first..last + 1 {
+                for q in first_q..last_q + 1 {
+                    quarters.push(day * 4 + q);
+                }
+            }
+        }
+        quarters.sort();
+        quarters.dedup();
+
+        Ok(quarters)
+    }
+
+    /// Get the (0-based) index of a day of the week
+    fn get_day(day: &str, pattern: &str) -> Result<u32, String> {
+        match day {
+            "mon" => Ok(0),
+            "tue" => Ok(1),
+            "wed" => Ok(2),
+            "thu" => Ok(3),
+            "fri" => Ok(4),
+            _ => Err(format!("Invalid day {} in pattern {}", day, pattern)),
+        }
+    }
+}
@@ -0,0 +1,35 @@
+/// A simple pseudo-random number generator (xorshift64*).
+///
+/// Simulations need to give the same results each time the chart is
+/// generated, so the generator is seeded from the config rather than
+/// from the system.
+#[derive(Debug, Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        // The state must never be zero.  Mix the seed so that nearby
+        // seeds don't give similar sequences.
+        let mut state = seed.wrapping_add(0x9e3779b97f4a7c15);
+        state = (state ^ (state >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        state = (state ^ (state >> 27)).wrapping_mul(0x94d049bb133111eb);
+        state ^= state >> 31;
+        Random { state: if state == 0 { 1 } else { state } }
+    }
+
+    /// Get the next number in the sequence
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+
+    /// Get a number that is at least 0, and less than 1
+    pub fn next_fraction(&mut self) -> f32 {
+        // Use the top 24 bits, which is all that an f32 can hold exactly.
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
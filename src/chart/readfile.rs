@@ -2,6 +2,7 @@ use std::io::prelude::*;
 use std::io::BufReader;
 use std::fs::File;
 use super::file::*;
+use super::timesheet::*;
 
 pub fn read_config(filename: &str) -> Result<ConfigLines, String> {
 
@@ -28,6 +29,21 @@ pub fn read_config(filename: &str) -> Result<ConfigLines, String> {
     Ok(file_data)
 }
 
+/// Read a CSV timesheet of the work that has been done
+pub fn read_timesheet(filename: &str) -> Result<Timesheet, String> {
+
+    let mut f = match File::open(filename) {
+        Ok(f) => f,
+        Err(err) => return Err(format!("Problem reading timesheet {}: {}", filename, err)),
+    };
+
+    let mut contents = String::new();
+    match f.read_to_string(&mut contents) {
+        Ok(_) => Ok(Timesheet::new(&contents)),
+        Err(err) => Err(format!("Problem reading timesheet {}: {}", filename, err)),
+    }
+}
+
 fn process_line(input_line: &str,
                 file_data: &mut ConfigLines,
                 line_num: u32)
@@ -0,0 +1,160 @@
+use super::duration::*;
+use super::time::*;
+
+/// A commitment that repeats every week, such as a team meeting.
+///
+/// This is given either by a commitment attribute with a range of weeks
+/// and a day, eg `C3..9/fri: 0.5`, or by a part of an "every" attribute,
+/// eg `every: fri.4 x 0.25`.
+/// - The weeks are a single week, eg `3`, a range, eg `3..9`, or an open
+///   range, eg `3..`, which runs to the end of the chart.  "every" is for
+///   every week of the chart.
+/// - The day is a day of the week (`mon` to `fri`) or its number (1 to
+///   5), optionally followed by the quarter of the day (1 to 4) that the
+///   commitment starts in, eg `fri.4`.
+/// - The amount is the number of days of each commitment.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Recurrence {
+    first_week: u32,
+    last_week: Option<u32>,
+    day: u32,
+    quarter: u32,
+    duration: Duration,
+}
+
+impl Recurrence {
+    /// Create a Recurrence from the key, without the leading 'C', and the
+    /// value of a commitment attribute, eg "3..9/fri" and "0.5".
+    pub fn new_from_commitment(key: &str, value: &str) -> Result<Recurrence, String> {
+
+        let desc = format!("C{}", key);
+        let v: Vec<&str> = key.split('/').collect();
+        if v.len() != 2 {
+            return Err(format!("Recurring commitment {} must be C<weeks>/<day>", desc));
+        }
+
+        let (first_week, last_week) = match v[0].find("..") {
+            Some(pos) => {
+                let first = try!(Recurrence::get_week(&v[0][..pos], &desc));
+                let last = match &v[0][pos + 2..] {
+                    "" => None,
+                    last => Some(try!(Recurrence::get_week(last, &desc))),
+                };
+                (first, last)
+            }
+            None => {
+                let week = try!(Recurrence::get_week(v[0], &desc));
+                (week, Some(week))
+            }
+        };
+        match last_week {
+            Some(last) if last < first_week => {
+                return Err(format!("Weeks are out of order in {}", desc));
+            }
+            _ => {}
+        };
+
+        let (day, quarter) = try!(Recurrence::get_day(v[1], &desc));
+        let duration = try!(Recurrence::get_duration(value, &desc));
+
+        Ok(Recurrence {
+               first_week: first_week,
+               last_week: last_week,
+               day: day,
+               quarter: quarter,
+               duration: duration,
+           })
+    }
+
+    /// Create a Recurrence for every week of the chart, from a part of an
+    /// "every" attribute, eg "fri.4 x 0.25".
+    pub fn new_from_every(desc: &str) -> Result<Recurrence, String> {
+
+        let v: Vec<&str> = desc.split(" x ").collect();
+        if v.len() != 2 {
+            return Err(format!("Recurring commitment {} must be <day> x <days>", desc));
+        }
+        let (day, quarter) = try!(Recurrence::get_day(v[0].trim(), desc));
+        let duration = try!(Recurrence::get_duration(v[1].trim(), desc));
+
+        Ok(Recurrence {
+               first_week: 1,
+               last_week: None,
+               day: day,
+               quarter: quarter,
+               duration: duration,
+           })
+    }
+
+    /// Get the commitment in each week of a chart
+    pub fn get_commitments(&self, weeks: u32) -> Vec<(ChartTime, Duration)> {
+
+        let last_week = match self.last_week {
+            Some(last) if last < weeks => last,
+            _ => weeks,
+        };
+        (self.first_week..last_week + 1)
+            .map(|week| {
+                     (ChartTime::new(&format!("{}.{}.{}", week, self.day, self.quarter))
+                          .unwrap(),
+                      self.duration)
+                 })
+            .collect()
+    }
+
+    fn get_week(week: &str, desc: &str) -> Result<u32, String> {
+        match week.parse::<u32>() {
+            Ok(w) if w > 0 => Ok(w),
+            _ => Err(format!("Invalid week {} in recurring commitment {}", week, desc)),
+        }
+    }
+
+    /// Get the day (1 to 5) and quarter (1 to 4) from a day and optional
+    /// quarter, eg "fri.4".
+    fn get_day(day: &str, desc: &str) -> Result<(u32, u32), String> {
+
+        let v: Vec<&str> = day.split('.').collect();
+        if v.len() > 2 {
+            return Err(format!("Invalid day {} in recurring commitment {}", day, desc));
+        }
+        let d = match v[0] {
+            "mon" => 1,
+            "tue" => 2,
+            "wed" => 3,
+            "thu" => 4,
+            "fri" => 5,
+            num => {
+                match num.parse::<u32>() {
+                    Ok(d) if (1..=5).contains(&d) => d,
+                    _ => {
+                        return Err(format!("Invalid day {} in recurring commitment {}",
+                                           v[0],
+                                           desc))
+                    }
+                }
+            }
+        };
+        let q = match v.get(1) {
+            Some(q) => {
+                match q.parse::<u32>() {
+                    Ok(q) if (1..=4).contains(&q) => q,
+                    _ => {
+                        return Err(format!("Invalid quarter {} in recurring commitment {}",
+                                           q,
+                                           desc))
+                    }
+                }
+            }
+            None => 1,
+        };
+
+        Ok((d, q))
+    }
+
+    fn get_duration(days: &str, desc: &str) -> Result<Duration, String> {
+        match days.parse::<f32>() {
+            Ok(d) if d > 0.0 => Ok(Duration::new_days(d)),
+            _ => Err(format!("Invalid days {} in recurring commitment {}", days, desc)),
+        }
+    }
+}
@@ -1,11 +1,19 @@
+use super::date::*;
+use super::dependency::*;
 use super::duration::*;
 use super::time::*;
 use super::period::*;
+use super::person::*;
+use super::random::*;
+use super::recurrence::*;
 use super::timerow::*;
+use super::timesheet::*;
+use super::who::*;
 use super::nodes::*;
 use super::file::*;
 use super::SchedulingStrategy;
 use super::ResourcingStrategy;
+use super::DependencyType;
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::collections::HashMap;
@@ -55,6 +63,62 @@ fn time_test() {
     assert_eq!(t3.get_duration().quarters(), 1);
 }
 
+#[test]
+fn date_test() {
+    let date = ChartDate::new("2026-10-05").unwrap();
+    assert_eq!(format!("{}", date), "2026-10-05");
+    assert_eq!(date.get_weekday(), 0);
+    assert_eq!(ChartDate::new_from_days(date.get_days()), date);
+    assert_eq!(ChartDate::new("1970-01-01").unwrap().get_days(), 0);
+    assert_eq!(format!("{}", date.add_days(30)), "2026-11-04");
+    assert_eq!(format!("{}", ChartDate::new("2024-02-28").unwrap().add_days(1)),
+               "2024-02-29");
+    assert!(ChartDate::new("2026-02-29").is_err());
+    assert!(ChartDate::new("2026-13-01").is_err());
+    assert!(ChartDate::new("26-10-05").is_err());
+
+    // Dates can only be used as times if the chart has a start date
+    assert!(ChartTime::new("2026-10-07").is_err());
+    assert!(ChartTime::new_with_start("2026-10-07", None).is_err());
+
+    // Dates are counted from the Monday that the chart starts on
+    let start = Some(date);
+    assert_eq!(ChartTime::new_with_start("2026-10-07", start).unwrap(),
+               ChartTime::new("1.3").unwrap());
+    assert_eq!(ChartTime::new_with_start("2026-10-14.2", start).unwrap(),
+               ChartTime::new("2.3.2").unwrap());
+    assert_eq!(ChartTime::new_with_start("2.3", start).unwrap(),
+               ChartTime::new("2.3").unwrap());
+    assert_eq!(format!("{}", ChartTime::new("2.3").unwrap().get_date(&date)),
+               "2026-10-14");
+    assert_eq!(format!("{}", ChartTime::new("3").unwrap().get_date(&date)),
+               "2026-10-19");
+    assert!(ChartTime::new_with_start("2026-10-04", start).is_err());
+    assert!(ChartTime::new_with_start("2026-10-10", start).is_err());
+    assert!(ChartTime::new_with_start("2026-10-07.5", start).is_err());
+}
+
+#[test]
+fn dependency_test() {
+    let d1 = Dependency::new("PRD 12345/DCUT").unwrap();
+    assert_eq!(d1.get_path(), "PRD 12345/DCUT");
+    assert_eq!(d1.get_kind(), DependencyType::FinishToStart);
+    assert_eq!(d1.get_lag().quarters(), 0);
+
+    let d2 = Dependency::new("SS PRD 12345/DCUT + 2d").unwrap();
+    assert_eq!(d2.get_path(), "PRD 12345/DCUT");
+    assert_eq!(d2.get_kind(), DependencyType::StartToStart);
+    assert_eq!(d2.get_lag().quarters(), 8);
+
+    let d3 = Dependency::new("FF Overheads/Education/illness - 0.5").unwrap();
+    assert_eq!(d3.get_path(), "Overheads/Education/illness");
+    assert_eq!(d3.get_kind(), DependencyType::FinishToFinish);
+    assert_eq!(d3.get_lag().quarters(), -2);
+
+    assert!(Dependency::new("DCUT + 2 days").is_err());
+    assert!(Dependency::new("").is_err());
+}
+
 #[test]
 fn period_test() {
     let t = ChartTime::new("3").unwrap();
@@ -78,26 +142,63 @@ fn period_test() {
     assert_eq!(p2.union(&p5), None);
 }
 
+#[test]
+fn person_test() {
+    let availability = |desc: &str| {
+        PersonConfig::new(desc).unwrap().get_availability(10, None).unwrap().count()
+    };
+
+    assert_eq!(availability("1..10"), 200.0);
+    assert_eq!(availability("1..10 pattern=mon-thu"), 160.0);
+    assert_eq!(availability("1..4, 6..8.3"), 132.0);
+    assert_eq!(availability("1..2, !1.3"), 36.0);
+    assert_eq!(availability("1..1 pattern=mon-fri:am"), 10.0);
+    assert_eq!(availability("1..2 pattern=mon,wed:pm"), 12.0);
+    assert_eq!(availability("1..2 pattern=mon-tue,tue:am"), 16.0);
+
+    let person = PersonConfig::new("3..4 region=uk pattern=fri").unwrap();
+    assert_eq!(person.get_region(), Some("uk".to_string()));
+    let row = person.get_availability(10, None).unwrap();
+    assert_eq!(row.count(), 8.0);
+    assert!(row.is_set(56));
+    assert!(!row.is_set(55));
+
+    assert_eq!(availability("1..2 capacity=0.5"), 20.0);
+    assert_eq!(availability("1..1 pattern=mon capacity=1.25"), 5.0);
+
+    let person = PersonConfig::new("1..10 roles=backend,dba").unwrap();
+    assert!(person.has_role("dba"));
+    assert!(!person.has_role("frontend"));
+
+    assert!(PersonConfig::new("").is_err());
+    assert!(PersonConfig::new("1..10 capacity=lots").is_err());
+    assert!(PersonConfig::new("1..10 colour=red").is_err());
+    assert!(PersonConfig::new("1..10 pattern=sat").is_err());
+    assert!(PersonConfig::new("1..10 pattern=thu-mon").is_err());
+    assert!(PersonConfig::new("1..10 pattern=mon:eve").is_err());
+    assert!(PersonConfig::new("1..11").unwrap().get_availability(10, None).is_err());
+}
+
 #[test]
 fn timerow_test() {
     let mut g = ChartTimeRow::new();
 
-    assert!(g.count() == 0);
+    assert!(g.count() == 0.0);
     assert!(!g.is_set(23));
     g.set(23);
-    assert!(g.count() == 1);
+    assert!(g.count() == 1.0);
     assert!(g.is_set(23));
     assert!(!g.is_set(22));
     assert!(!g.is_set(24));
 
     assert!(!g.is_set(0));
     g.set(0);
-    assert!(g.count() == 2);
+    assert!(g.count() == 2.0);
     assert!(g.is_set(0));
     assert!(!g.is_set(1));
 
     g.set_range(0..50);
-    assert!(g.count() == 50);
+    assert!(g.count() == 50.0);
     assert!(g.is_set(1));
     assert!(g.is_set(0));
     assert!(g.is_set(49));
@@ -105,68 +206,126 @@ fn timerow_test() {
 
     // Successful smear
     let mut g2 = ChartTimeRow::new();
-    match g.smear_transfer_to(&mut g2, 10, 0..50) {
-        (Some(_), 10, 0) => assert!(true),
+    match g.smear_transfer_to(&mut g2, 10.0, 0..50) {
+        (Some(_), done, missed) if done == 10.0 && missed == 0.0 => assert!(true),
         _ => assert!(false),
     }
-    assert!(g.count() == 40);
-    assert!(g2.count() == 10);
+    assert!(g.count() == 40.0);
+    assert!(g2.count() == 10.0);
 
     // Smear that required multiple passes
     g2.set_range(25..50);
-    assert_eq!(g2.count(), 30);
-    match g.smear_transfer_to(&mut g2, 10, 0..50) {
-        (Some(_), 10, 0) => assert!(true),
+    assert_eq!(g2.count(), 30.0);
+    match g.smear_transfer_to(&mut g2, 10.0, 0..50) {
+        (Some(_), done, missed) if done == 10.0 && missed == 0.0 => assert!(true),
         _ => assert!(false),
     }
-    assert_eq!(g.count(), 30);
-    assert_eq!(g2.count(), 40);
+    assert_eq!(g.count(), 30.0);
+    assert_eq!(g2.count(), 40.0);
 
     // Failure to smear
-    match g.smear_transfer_to(&mut g2, 11, 0..50) {
-        (Some(_), 10, 1) => assert!(true),
+    match g.smear_transfer_to(&mut g2, 11.0, 0..50) {
+        (Some(_), done, missed) if done == 10.0 && missed == 1.0 => assert!(true),
         _ => assert!(false),
     }
 
     // Successful fill
     g2 = ChartTimeRow::new();
     g.set_range(0..50);
-    match g.fill_transfer_to(&mut g2, 10, 5..20) {
-        (Some(14), 10, 0) => assert!(true),
+    match g.fill_transfer_to(&mut g2, 10.0, 5..20) {
+        (Some(14), done, missed) if done == 10.0 && missed == 0.0 => assert!(true),
         _ => assert!(false),
     }
 
     // Another successful fill
-    match g.fill_transfer_to(&mut g2, 2, 4..20) {
-        (Some(15), 2, 0) => assert!(true),
+    match g.fill_transfer_to(&mut g2, 2.0, 4..20) {
+        (Some(15), done, missed) if done == 2.0 && missed == 0.0 => assert!(true),
         _ => assert!(false),
     }
 
     // Failure to allocate
-    match g.fill_transfer_to(&mut g2, 2, 4..17) {
-        (Some(16), 1, 1) => assert!(true),
+    match g.fill_transfer_to(&mut g2, 2.0, 4..17) {
+        (Some(16), done, missed) if done == 1.0 && missed == 1.0 => assert!(true),
         _ => assert!(false),
     }
 
     // Successful fill
     g2 = ChartTimeRow::new();
     g.set_range(0..50);
-    match g.reverse_fill_transfer_to(&mut g2, 10, 25..40) {
-        Ok(last) => assert!(last == 30),
-        Err(_) => assert!(false),
+    match g.reverse_fill_transfer_to(&mut g2, 10.0, 25..40) {
+        (Some(30), done, missed) if done == 10.0 && missed == 0.0 => assert!(true),
+        _ => assert!(false),
     }
 
     // Another successful fill
-    match g.reverse_fill_transfer_to(&mut g2, 2, 4..41) {
-        Ok(last) => assert!(last == 29),
-        Err(_) => assert!(false),
+    match g.reverse_fill_transfer_to(&mut g2, 2.0, 4..41) {
+        (Some(29), done, missed) if done == 2.0 && missed == 0.0 => assert!(true),
+        _ => assert!(false),
     }
 
     // Failure to allocate
-    match g.reverse_fill_transfer_to(&mut g2, 2, 28..41) {
-        Ok(_) => assert!(false),
-        Err(unallocated) => assert!(unallocated == 1),
+    match g.reverse_fill_transfer_to(&mut g2, 2.0, 28..41) {
+        (Some(28), done, missed) if done == 1.0 && missed == 1.0 => assert!(true),
+        _ => assert!(false),
+    }
+
+    // Period spanned by the allocated cells
+    assert_eq!(g2.get_period(0..50), ChartPeriod::new(28, 40));
+    assert_eq!(g2.get_period(35..38), ChartPeriod::new(35, 37));
+    assert_eq!(g2.get_period(41..50), None);
+
+    // Runs of cells are split and merged as cells change
+    let mut runs = ChartTimeRow::new();
+    runs.set_range(0..8);
+    runs.set_value(3, 0.5);
+    runs.unset(5);
+    assert_eq!(format!("{}", runs), "[ooo.o_oo]");
+    assert_eq!(runs.count_range(2..7), 3.5);
+    assert_eq!(runs.get_period(5..6), None);
+    assert_eq!(runs.get_period(4..20), ChartPeriod::new(4, 7));
+    runs.set(5);
+    runs.set(3);
+    assert_eq!(format!("{}", runs), "[oooooooo]");
+    runs.set_range(10..12);
+    let mut other = ChartTimeRow::new();
+    other.set_range(6..11);
+    runs.unset_row(&other);
+    assert_eq!(format!("{}", runs), "[oooooo_____o]");
+
+    // Cells with fractional capacity
+    let mut part = ChartTimeRow::new();
+    part.set_range(0..4);
+    part.scale(0.5);
+    assert_eq!(part.count(), 2.0);
+    assert_eq!(format!("{}", part), "[....]");
+    let mut task_a = ChartTimeRow::new();
+    let mut task_b = ChartTimeRow::new();
+    match part.fill_transfer_to(&mut task_a, 0.75, 0..4) {
+        (Some(1), done, missed) if done == 0.75 && missed == 0.0 => assert!(true),
+        _ => assert!(false),
+    }
+    assert_eq!(task_a.get(1), 0.25);
+    match part.smear_transfer_to(&mut task_b, 2.0, 0..4) {
+        (Some(3), done, missed) if done == 1.25 && missed == 0.75 => assert!(true),
+        _ => assert!(false),
+    }
+    assert_eq!(part.count(), 0.0);
+    assert_eq!(task_a.count_range(0..4) + task_b.count_range(0..4), 2.0);
+
+    // Overtime, split between two tasks in the same quarter
+    let mut overtime = ChartTimeRow::new();
+    overtime.set_value(0, 1.5);
+    task_a = ChartTimeRow::new();
+    task_b = ChartTimeRow::new();
+    match overtime.fill_transfer_to(&mut task_a, 2.0, 0..1) {
+        (Some(0), done, missed) if done == 1.0 && missed == 1.0 => assert!(true),
+        _ => assert!(false),
     }
+    match overtime.fill_transfer_to(&mut task_b, 2.0, 0..1) {
+        (Some(0), done, missed) if done == 0.5 && missed == 1.5 => assert!(true),
+        _ => assert!(false),
+    }
+    assert_eq!(task_b.get_weekly_summary(1), "0.5");
 
     // Test row display
     assert_eq!(g2.get_weekly_summary(3), "    12  1".to_string());
@@ -222,6 +381,7 @@ fn nodes_test() {
     f.add_line(Line::new_attribute_line("earliest-start", "2.3"));
     f.add_line(Line::new_attribute_line("latest-end", "2.4.3"));
     f.add_line(Line::new_attribute_line("non-managed", "true"));
+    f.add_line(Line::new_attribute_line("sfr-split", "30"));
 
     f.add_line(Line::new_node_line(8, 6, "greatgrandchild"));
     f.add_line(Line::new_attribute_line("C2.3.3", "1.5"));
@@ -240,6 +400,7 @@ fn nodes_test() {
     f.add_line(Line::new_attribute_line("schedule", "serial"));
     f.add_line(Line::new_node_line(20, 1, "sibling3"));
     f.add_line(Line::new_attribute_line("plan", "5, 2:10, 3:20"));
+    f.add_line(Line::new_attribute_line("budget", "ten"));
 
     f.add_line(Line::new_node_line(21, 1, "[chart]"));
     f.add_line(Line::new_attribute_line("weeks", "10"));
@@ -323,8 +484,16 @@ fn nodes_test() {
                None);
 
     // Test budget retrieval on nodes that do, and don't, include one,
-    assert_eq!(grandchild.borrow().get_budget(), None);
-    assert_eq!(fred.borrow().get_budget(), Some(Duration::new_days(10f32)));
+    assert_eq!(grandchild.borrow().get_budget(), Ok(None));
+    assert_eq!(fred.borrow().get_budget(), Ok(Some(Duration::new_days(10f32))));
+    assert_eq!(sibling3.borrow().get_budget(),
+               Err("Problem in node at line 20: Invalid budget ten: not a number of days"
+                       .to_string()));
+
+    // Test summing plans over a budget node's children.  As nothing has
+    // been allocated, all of the plan remains.
+    assert_eq!(fred.borrow().get_summed_plan(&root_ref, 10, &ChartTime::new("1").unwrap()),
+               (Duration::new_days(3.0), Duration::new_days(3.0)));
 
     // Test "who" retrieval on nodes where:
     // - Local value is available
@@ -447,6 +616,14 @@ fn nodes_test() {
                    .unwrap(),
                ResourcingStrategy::SmearRemaining);
 
+    // Test sfr-split retrieval on nodes where:
+    // - There is a local value
+    // - There is an inherited value
+    // - There is no value
+    assert_eq!(grandchild.borrow().get_sfr_split().unwrap(), 30);
+    assert_eq!(greatgrandchild.borrow().get_sfr_split().unwrap(), 30);
+    assert_eq!(child.borrow().get_sfr_split().unwrap(), 20);
+
     // Test commitment retrieval on nodes where:
     // - There are no commitments
     // - There are no commitments, but there is an attribute starting with C.
@@ -531,13 +708,1449 @@ fn nodes_test() {
     assert!(h2.contains_key("rf"));
     assert!(h2.contains_key("rsl"));
     assert_eq!(h2.len(), 2);
-    assert_eq!(h2.get("rf").unwrap().count(), 200);
+    assert_eq!(h2.get("rf").unwrap().count(), 200.0);
     assert!(h2.get("rf").unwrap().is_set(0));
     assert!(h2.get("rf").unwrap().is_set(199));
     assert!(!h2.get("rf").unwrap().is_set(200));
-    assert_eq!(h2.get("rsl").unwrap().count(), 152);
+    assert_eq!(h2.get("rsl").unwrap().count(), 152.0);
     assert!(!h2.get("rsl").unwrap().is_set(19));
     assert!(h2.get("rsl").unwrap().is_set(20));
     assert!(h2.get("rsl").unwrap().is_set(171));
     assert!(!h2.get("rsl").unwrap().is_set(172));
 }
+
+#[test]
+fn allocation_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "4"));
+    f.add_line(Line::new_attribute_line("today", "2"));
+
+    f.add_line(Line::new_node_line(4, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("rf", "1..4"));
+    f.add_line(Line::new_attribute_line("bg5", "3..4"));
+
+    f.add_line(Line::new_node_line(6, 1, "front"));
+    f.add_line(Line::new_attribute_line("who", "rf"));
+    f.add_line(Line::new_attribute_line("resource", "frontload"));
+    f.add_line(Line::new_attribute_line("non-managed", "true"));
+    f.add_line(Line::new_attribute_line("plan", "2"));
+
+    f.add_line(Line::new_node_line(11, 1, "back"));
+    f.add_line(Line::new_attribute_line("who", "rf"));
+    f.add_line(Line::new_attribute_line("resource", "backload"));
+    f.add_line(Line::new_attribute_line("non-managed", "true"));
+    f.add_line(Line::new_attribute_line("plan", "3"));
+    f.add_line(Line::new_attribute_line("latest-end", "4.4.4"));
+
+    f.add_line(Line::new_node_line(17, 1, "sfr"));
+    f.add_line(Line::new_attribute_line("who", "rf"));
+    f.add_line(Line::new_attribute_line("resource", "prodsfr"));
+    f.add_line(Line::new_attribute_line("sfr-split", "50"));
+    f.add_line(Line::new_attribute_line("non-managed", "true"));
+    f.add_line(Line::new_attribute_line("plan", "2"));
+
+    f.add_line(Line::new_node_line(24, 1, "bg5"));
+    f.add_line(Line::new_attribute_line("resource", "smearprorata"));
+    f.add_line(Line::new_attribute_line("non-managed", "true"));
+    f.add_line(Line::new_attribute_line("plan", "52pcy"));
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+
+    let root_ref = rc_root.borrow();
+    let front = root_ref.get_node_at_line(6)
+        .unwrap()
+        .upgrade()
+        .unwrap();
+    let back = root_ref.get_node_at_line(11)
+        .unwrap()
+        .upgrade()
+        .unwrap();
+
+    // Front-loaded work starts today; back-loaded work is filled
+    // backwards from the end of Thursday in week 4.
+    assert_eq!(front.borrow().get_allocation(), ChartPeriod::new(20, 27));
+    assert_eq!(back.borrow().get_allocation(), ChartPeriod::new(64, 75));
+
+    // Half of the SFR time is back-filled at the end of the chart, and
+    // the other half smeared over the free time from today.
+    let sfr = root_ref.get_node_at_line(17)
+        .unwrap()
+        .upgrade()
+        .unwrap();
+    let sfr_period = sfr.borrow().get_allocation().unwrap();
+    assert_eq!(sfr_period.get_last(), 79);
+    assert!(sfr_period.get_first() < 64);
+
+    // 52pcy is pro-rated over the two weeks that bg5 is in the plan, and
+    // smeared over just those weeks.
+    let bg5 = root_ref.get_node_at_line(24)
+        .unwrap()
+        .upgrade()
+        .unwrap();
+    let bg5_availability = bg5.borrow().get_owner_availability(&root_ref, 4);
+    assert_eq!(bg5_availability.count(), 40.0);
+    assert_eq!(bg5.borrow()
+                   .get_plan(&ChartTime::new("5").unwrap(),
+                             &Duration::new_quarters(bg5_availability.count() as i32))
+                   .unwrap()
+                   .unwrap()
+                   .quarters(),
+               8);
+    let bg5_period = bg5.borrow().get_allocation().unwrap();
+    assert!(bg5_period.get_first() >= 40);
+    assert!(bg5_period.get_last() > 70);
+}
+
+#[test]
+fn dependency_allocation_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file.  Every task is front-loaded, and a full
+    // day long unless stated otherwise.
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "4"));
+
+    f.add_line(Line::new_node_line(3, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("rf", "1..4"));
+    f.add_line(Line::new_attribute_line("jw2", "1..4"));
+
+    f.add_line(Line::new_node_line(6, 1, "tasks"));
+    f.add_line(Line::new_attribute_line("resource", "frontload"));
+    f.add_line(Line::new_attribute_line("non-managed", "true"));
+    f.add_line(Line::new_attribute_line("default-plan", "1"));
+
+    // Depends on a task later in the file
+    f.add_line(Line::new_node_line(10, 2, "H"));
+    f.add_line(Line::new_attribute_line("who", "rf"));
+    f.add_line(Line::new_attribute_line("after", "tasks/I"));
+
+    f.add_line(Line::new_node_line(13, 2, "A"));
+    f.add_line(Line::new_attribute_line("who", "rf"));
+    f.add_line(Line::new_attribute_line("plan", "2"));
+
+    f.add_line(Line::new_node_line(16, 2, "B"));
+    f.add_line(Line::new_attribute_line("who", "jw2"));
+    f.add_line(Line::new_attribute_line("plan", "2"));
+    f.add_line(Line::new_attribute_line("after", "tasks/A + 1d"));
+
+    f.add_line(Line::new_node_line(20, 2, "C"));
+    f.add_line(Line::new_attribute_line("who", "jw2"));
+    f.add_line(Line::new_attribute_line("after", "SS tasks/A"));
+
+    f.add_line(Line::new_node_line(23, 2, "D"));
+    f.add_line(Line::new_attribute_line("who", "jw2"));
+    f.add_line(Line::new_attribute_line("after", "FF tasks/B"));
+
+    f.add_line(Line::new_node_line(26, 2, "I"));
+    f.add_line(Line::new_attribute_line("who", "rf"));
+
+    f.add_line(Line::new_node_line(28, 2, "E"));
+    f.add_line(Line::new_attribute_line("who", "rf"));
+    f.add_line(Line::new_attribute_line("after", "tasks/F"));
+
+    f.add_line(Line::new_node_line(31, 2, "F"));
+    f.add_line(Line::new_attribute_line("who", "rf"));
+    f.add_line(Line::new_attribute_line("after", "tasks/E"));
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+
+    let root_ref = rc_root.borrow();
+    let allocation = |line: u32| {
+        root_ref.get_node_at_line(line)
+            .unwrap()
+            .upgrade()
+            .unwrap()
+            .borrow()
+            .get_allocation()
+    };
+
+    // H waits for I, which is allocated after A
+    assert_eq!(allocation(13), ChartPeriod::new(0, 7));
+    assert_eq!(allocation(26), ChartPeriod::new(8, 11));
+    assert_eq!(allocation(10), ChartPeriod::new(12, 15));
+
+    // B starts a day after A finishes
+    assert_eq!(allocation(16), ChartPeriod::new(12, 19));
+
+    // C can start with A
+    assert_eq!(allocation(20), ChartPeriod::new(0, 3));
+
+    // D can't finish before B, so the last quarter is held back
+    assert_eq!(allocation(23), ChartPeriod::new(4, 20));
+
+    // E and F are in a cycle, so are not allocated
+    assert_eq!(allocation(28), None);
+    assert_eq!(allocation(31), None);
+
+    // A can slip as far as B can, but not at all without delaying B.
+    // B can slip a quarter before it delays D finishing.
+    let slack = |line: u32| {
+        root_ref.get_node_at_line(line)
+            .unwrap()
+            .upgrade()
+            .unwrap()
+            .borrow()
+            .get_slack()
+    };
+    assert_eq!(slack(13),
+               Some((Duration::new_days(15.0), Duration::new_quarters(0))));
+    assert_eq!(slack(16),
+               Some((Duration::new_days(15.0), Duration::new_quarters(1))));
+    assert_eq!(slack(28), None);
+
+    // The parent takes the smallest slack of its children
+    assert_eq!(slack(6).unwrap().1, Duration::new_quarters(0));
+}
+
+#[test]
+fn managed_dependency_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "2"));
+
+    f.add_line(Line::new_node_line(3, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("a", "1.."));
+
+    f.add_line(Line::new_node_line(5, 1, "tasks"));
+    f.add_line(Line::new_attribute_line("resource", "frontload"));
+    f.add_line(Line::new_attribute_line("who", "a"));
+    f.add_line(Line::new_node_line(8, 2, "Build"));
+    f.add_line(Line::new_attribute_line("plan", "2"));
+    f.add_line(Line::new_node_line(10, 2, "Release"));
+    f.add_line(Line::new_attribute_line("plan", "1"));
+    f.add_line(Line::new_attribute_line("non-managed", "true"));
+    f.add_line(Line::new_attribute_line("after", "tasks/Build"));
+    f.add_line(Line::new_node_line(14, 2, "Orphan"));
+    f.add_line(Line::new_attribute_line("plan", "1"));
+    f.add_line(Line::new_attribute_line("who", "nobody"));
+    f.add_line(Line::new_node_line(17, 2, "Blocked"));
+    f.add_line(Line::new_attribute_line("plan", "1"));
+    f.add_line(Line::new_attribute_line("non-managed", "true"));
+    f.add_line(Line::new_attribute_line("after", "tasks/Orphan"));
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+
+    let root_ref = rc_root.borrow();
+    let task = |line| root_ref.get_node_at_line(line).unwrap().upgrade().unwrap();
+
+    // Release isn't managed, but still waits for Build
+    assert_eq!(task(8).borrow().get_allocation(),
+               Some(ChartPeriod::new(0, 7).unwrap()));
+    assert_eq!(task(10).borrow().get_allocation(),
+               Some(ChartPeriod::new(8, 11).unwrap()));
+    assert_eq!(task(10).borrow().get_notes(), Vec::<String>::new());
+
+    // Orphan can't be allocated, so Blocked goes ahead without it
+    assert_eq!(task(17).borrow().get_allocation(),
+               Some(ChartPeriod::new(12, 15).unwrap()));
+    assert_eq!(task(17).borrow().get_notes(),
+               vec!["Dependency on tasks/Orphan was ignored, as it isn't allocated"
+                        .to_string()]);
+}
+
+#[test]
+fn plan_reconciliation_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "10"));
+
+    f.add_line(Line::new_node_line(3, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("rf", "1.."));
+
+    f.add_line(Line::new_node_line(5, 1, "split"));
+    f.add_line(Line::new_attribute_line("plan", "10.25"));
+    f.add_line(Line::new_attribute_line("split-plan", "true"));
+    f.add_line(Line::new_node_line(8, 2, "a"));
+    f.add_line(Line::new_attribute_line("plan", "4"));
+    f.add_line(Line::new_node_line(10, 2, "b"));
+    f.add_line(Line::new_node_line(11, 2, "c"));
+
+    f.add_line(Line::new_node_line(12, 1, "mismatch"));
+    f.add_line(Line::new_attribute_line("plan", "5"));
+    f.add_line(Line::new_node_line(14, 2, "d"));
+    f.add_line(Line::new_attribute_line("plan", "2"));
+    f.add_line(Line::new_node_line(16, 2, "e"));
+    f.add_line(Line::new_attribute_line("plan", "2"));
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+
+    let root_ref = rc_root.borrow();
+    let node = |line: u32| root_ref.get_node_at_line(line).unwrap().upgrade().unwrap();
+    let when = ChartTime::new("11").unwrap();
+    let ten_weeks = Duration::new_days(50.0);
+
+    // The 6.25 days left over are split between b and c
+    assert_eq!(node(10).borrow().get_plan(&when, &ten_weeks).unwrap(),
+               Some(Duration::new_days(3.25)));
+    assert_eq!(node(11).borrow().get_plan(&when, &ten_weeks).unwrap(),
+               Some(Duration::new_days(3.0)));
+    assert_eq!(node(5).borrow().get_notes().len(), 0);
+
+    // The children of "mismatch" only add up to 4 days
+    assert_eq!(node(12).borrow().get_notes(),
+               vec!["Plan of 5 days does not match the 4 days planned for the children"
+                        .to_string()]);
+}
+
+#[test]
+fn gain_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "5"));
+
+    f.add_line(Line::new_node_line(3, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("rf", "1.."));
+
+    f.add_line(Line::new_node_line(5, 1, "parent"));
+    f.add_line(Line::new_attribute_line("resource", "frontload"));
+    f.add_line(Line::new_node_line(7, 2, "task"));
+    f.add_line(Line::new_attribute_line("who", "rf"));
+    f.add_line(Line::new_attribute_line("plan", "10, 3:12"));
+    f.add_line(Line::new_attribute_line("gain", "2:-1, 4:2"));
+    f.add_line(Line::new_node_line(11, 2, "bad"));
+    f.add_line(Line::new_attribute_line("gain", "2"));
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+
+    let root_ref = rc_root.borrow();
+    let node = |line: u32| root_ref.get_node_at_line(line).unwrap().upgrade().unwrap();
+    let five_weeks = Duration::new_days(25.0);
+    let estimate = |when: &str| {
+        node(7)
+            .borrow()
+            .get_estimate(&ChartTime::new(when).unwrap(), &five_weeks)
+            .unwrap()
+            .unwrap()
+            .days()
+    };
+
+    // Gains leave the plan unchanged, but change the estimate
+    assert_eq!(node(7).borrow().get_plan(&ChartTime::new("6").unwrap(), &five_weeks).unwrap(),
+               Some(Duration::new_days(12.0)));
+    assert_eq!(estimate("1"), 10.0);
+    assert_eq!(estimate("2"), 11.0);
+    assert_eq!(estimate("3"), 13.0);
+    assert_eq!(estimate("4"), 11.0);
+    assert_eq!(estimate("6"), 11.0);
+
+    // The remaining estimate is allocated
+    assert_eq!(node(7).borrow().get_allocation(),
+               Some(ChartPeriod::new(0, 43).unwrap()));
+
+    // Parents sum the gains of their children
+    let trend: Vec<f32> = node(5)
+        .borrow()
+        .get_gain_trend(&root_ref, 5)
+        .iter()
+        .map(|g| g.days())
+        .collect();
+    assert_eq!(trend, vec![0.0, -1.0, -3.0, -1.0, -1.0]);
+
+    // Every gain needs a time
+    assert_eq!(node(11).borrow().get_gains(),
+               Err("Problem in node at line 11: Invalid gain part, 2 must be <time>:<days>"
+                       .to_string()));
+}
+
+#[test]
+fn calendar_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "5"));
+    f.add_line(Line::new_attribute_line("start-date", "2026-10-07"));
+    f.add_line(Line::new_attribute_line("today", "2026-10-14"));
+
+    f.add_line(Line::new_node_line(5, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("rf", "1..2026-10-30"));
+
+    f.add_line(Line::new_node_line(7, 1, "task"));
+    f.add_line(Line::new_attribute_line("who", "rf"));
+    f.add_line(Line::new_attribute_line("plan", "2"));
+    f.add_line(Line::new_attribute_line("resource", "frontload"));
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+
+    // A start date part way through a week starts the chart on the Monday
+    let root_ref = rc_root.borrow();
+    assert_eq!(root_ref.get_start_date().unwrap(),
+               Some(ChartDate::new("2026-10-05").unwrap()));
+    assert_eq!(root_ref.get_today().unwrap(), ChartTime::new("2.3").unwrap());
+
+    // Allocation starts today, and availability ends on the given date
+    let task = root_ref.get_node_at_line(7).unwrap().upgrade().unwrap();
+    assert_eq!(task.borrow().get_allocation(),
+               Some(ChartPeriod::new(28, 35).unwrap()));
+    assert_eq!(task.borrow().get_owner_availability(&root_ref, 5).count(), 80.0);
+
+    // Another chart, starting a week later, reads the same date as a
+    // different time.
+    let rc_other = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "5"));
+    f.add_line(Line::new_attribute_line("start-date", "2026-10-12"));
+    f.add_line(Line::new_attribute_line("today", "2026-10-14"));
+    rc_other.borrow_mut().consume_config(Some(&rc_other), &mut f).unwrap();
+    assert_eq!(rc_other.borrow().get_today().unwrap(), ChartTime::new("1.3").unwrap());
+    assert_eq!(root_ref.get_today().unwrap(), ChartTime::new("2.3").unwrap());
+    assert_eq!(task.borrow().new_time("2026-10-14").unwrap(),
+               ChartTime::new("2.3").unwrap());
+}
+
+#[test]
+fn holiday_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "4"));
+    f.add_line(Line::new_attribute_line("start-date", "2026-10-05"));
+    f.add_line(Line::new_attribute_line("today", "1"));
+
+    f.add_line(Line::new_node_line(5, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("rf", "1.. region=uk"));
+    f.add_line(Line::new_attribute_line("jw2", "1.. region=us"));
+
+    f.add_line(Line::new_node_line(8, 1, "[holidays]"));
+    f.add_line(Line::new_attribute_line("2026-10-07", "all"));
+    f.add_line(Line::new_attribute_line("2026-10-12..2026-10-13", "uk, ie"));
+    f.add_line(Line::new_attribute_line("2026-10-10", "all"));
+    f.add_line(Line::new_attribute_line("2026-12-25", "all"));
+    f.add_line(Line::new_attribute_line("2026-10-23..2026-10-25", "all"));
+    f.add_line(Line::new_attribute_line("2026-10-01..2026-10-05", "us"));
+    f.add_line(Line::new_attribute_line("2026-09-28..2026-10-02", "uk"));
+
+    f.add_line(Line::new_node_line(16, 1, "task"));
+    f.add_line(Line::new_attribute_line("who", "rf"));
+    f.add_line(Line::new_attribute_line("plan", "3"));
+    f.add_line(Line::new_attribute_line("resource", "frontload"));
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+
+    // The holiday that ends on a Sunday is just the Friday, the one that
+    // starts before the chart is just its first Monday, and the one that
+    // is over before the chart starts is ignored.
+    let root_ref = rc_root.borrow();
+    let holidays = root_ref.get_holidays(4).unwrap();
+    assert_eq!(holidays["rf"].count(), 16.0);
+    assert_eq!(holidays["jw2"].count(), 12.0);
+    assert!(!holidays["jw2"].is_set(4));
+    assert_eq!(holidays["jw2"].count_range(0..4), 4.0);
+    assert_eq!(holidays["jw2"].count_range(56..60), 4.0);
+
+    let people = root_ref.get_people(4).unwrap();
+    assert_eq!(people["rf"].count(), 64.0);
+    assert_eq!(people["jw2"].count(), 68.0);
+    assert!(!people["rf"].is_set(8));
+    assert!(!people["rf"].is_set(20));
+
+    // The task skips the holiday on the Wednesday
+    let task = root_ref.get_node_at_line(16).unwrap().upgrade().unwrap();
+    assert_eq!(task.borrow().get_allocation(),
+               Some(ChartPeriod::new(0, 15).unwrap()));
+    assert_eq!(task.borrow().get_owner_holidays(&root_ref).get_weekly_numbers(4),
+               vec![4.0, 8.0, 4.0, 0.0]);
+}
+
+#[test]
+fn who_test() {
+    let who = Who::new("rsl").unwrap();
+    assert_eq!(who.get_people(), &vec!["rsl".to_string()]);
+    assert_eq!(who.get_groups(), vec![(vec!["rsl".to_string()], 1.0)]);
+
+    let who = Who::new("rsl 70%, jw2 30%").unwrap();
+    assert_eq!(who.get_groups(),
+               vec![(vec!["rsl".to_string()], 0.7), (vec!["jw2".to_string()], 0.3)]);
+    assert_eq!(format!("{}", who), "rsl 70%, jw2 30%");
+
+    let who = Who::new("rsl, jw2").unwrap();
+    assert_eq!(who.get_groups(),
+               vec![(vec!["rsl".to_string()], 0.5), (vec!["jw2".to_string()], 0.5)]);
+
+    let who = Who::new("pool(rsl, jw2)").unwrap();
+    assert!(who.is_pooled());
+    assert_eq!(who.get_groups(),
+               vec![(vec!["rsl".to_string(), "jw2".to_string()], 1.0)]);
+    assert_eq!(format!("{}", who), "pool(rsl, jw2)");
+
+    let who = Who::new("any(backend)").unwrap();
+    assert_eq!(who.get_role(), Some(&"backend".to_string()));
+    assert!(who.get_groups().is_empty());
+    assert_eq!(format!("{}", who), "any(backend)");
+
+    assert!(Who::new("").is_err());
+    assert!(Who::new("any(backend, dba)").is_err());
+    assert!(Who::new("rsl, rsl").is_err());
+    assert!(Who::new("rsl 70%, jw2").is_err());
+    assert!(Who::new("rsl 70%, jw2 20%").is_err());
+    assert!(Who::new("rsl 70").is_err());
+    assert!(Who::new("pool(rsl 70%, jw2 30%)").is_err());
+}
+
+#[test]
+fn multi_owner_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "4"));
+    f.add_line(Line::new_attribute_line("today", "1"));
+
+    f.add_line(Line::new_node_line(4, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("a", "1.."));
+    f.add_line(Line::new_attribute_line("b", "1.."));
+    f.add_line(Line::new_attribute_line("c", "1.4.."));
+
+    f.add_line(Line::new_node_line(8, 1, "Tasks"));
+    f.add_line(Line::new_attribute_line("resource", "frontload"));
+    f.add_line(Line::new_node_line(10, 2, "Split"));
+    f.add_line(Line::new_attribute_line("who", "a 75%, b 25%"));
+    f.add_line(Line::new_attribute_line("plan", "8"));
+    f.add_line(Line::new_node_line(13, 2, "Pool"));
+    f.add_line(Line::new_attribute_line("who", "pool(c, b)"));
+    f.add_line(Line::new_attribute_line("plan", "5"));
+    f.add_line(Line::new_node_line(16, 2, "Meeting"));
+    f.add_line(Line::new_attribute_line("who", "a, b"));
+    f.add_line(Line::new_attribute_line("C1.1", "1"));
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+
+    let root_ref = rc_root.borrow();
+
+    // The split task is shared 3:1
+    let split = root_ref.get_node_at_line(10).unwrap().upgrade().unwrap();
+    assert_eq!(split.borrow().get_contribution("a").unwrap().count(), 24.0);
+    assert_eq!(split.borrow().get_contribution("b").unwrap().count(), 8.0);
+    assert_eq!(split.borrow().get_allocation(),
+               Some(ChartPeriod::new(0, 23).unwrap()));
+
+    // The pool prefers c, but b is free first
+    let pool = root_ref.get_node_at_line(13).unwrap().upgrade().unwrap();
+    let b = pool.borrow().get_contribution("b").unwrap();
+    assert_eq!(b.get_period(0..80), Some(ChartPeriod::new(8, 11).unwrap()));
+    let c = pool.borrow().get_contribution("c").unwrap();
+    assert_eq!(c.get_period(0..80), Some(ChartPeriod::new(12, 27).unwrap()));
+    assert_eq!(pool.borrow().get_allocation(),
+               Some(ChartPeriod::new(8, 27).unwrap()));
+
+    // It isn't known which owner did the committed work
+    let meeting = root_ref.get_node_at_line(16).unwrap().upgrade().unwrap();
+    assert!(meeting.borrow().get_contribution("a").is_none());
+    assert_eq!(meeting.borrow().get_notes(),
+               vec!["Commitments are ignored on a task with several owners; record each \
+                     person's work in a timesheet"
+                        .to_string()]);
+}
+
+#[test]
+fn role_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "4"));
+    f.add_line(Line::new_attribute_line("today", "1"));
+
+    f.add_line(Line::new_node_line(4, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("a", "1.. roles=backend"));
+    f.add_line(Line::new_attribute_line("b", "1.. roles=backend,frontend"));
+    f.add_line(Line::new_attribute_line("c", "1.. roles=frontend"));
+
+    f.add_line(Line::new_node_line(8, 1, "Tasks"));
+    f.add_line(Line::new_attribute_line("resource", "frontload"));
+    f.add_line(Line::new_node_line(10, 2, "Named"));
+    f.add_line(Line::new_attribute_line("who", "a"));
+    f.add_line(Line::new_attribute_line("plan", "5"));
+    f.add_line(Line::new_node_line(13, 2, "Backend 1"));
+    f.add_line(Line::new_attribute_line("who", "any(backend)"));
+    f.add_line(Line::new_attribute_line("plan", "2"));
+    f.add_line(Line::new_node_line(16, 2, "Backend 2"));
+    f.add_line(Line::new_attribute_line("who", "any(backend)"));
+    f.add_line(Line::new_attribute_line("plan", "2"));
+    f.add_line(Line::new_node_line(19, 2, "Frontend"));
+    f.add_line(Line::new_attribute_line("who", "any(frontend)"));
+    f.add_line(Line::new_attribute_line("plan", "3"));
+    f.add_line(Line::new_node_line(22, 2, "Testing"));
+    f.add_line(Line::new_attribute_line("who", "any(tester)"));
+    f.add_line(Line::new_attribute_line("plan", "1"));
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+
+    let root_ref = rc_root.borrow();
+    let valid: Vec<String> = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+    let task = |line| root_ref.get_node_at_line(line).unwrap().upgrade().unwrap();
+
+    // a is busy, so both backend tasks go to b
+    assert_eq!(task(13).borrow().get_who(&valid), Ok(Some("b".to_string())));
+    assert_eq!(task(16).borrow().get_who(&valid), Ok(Some("b".to_string())));
+    assert_eq!(task(16).borrow().get_allocation(),
+               Some(ChartPeriod::new(8, 15).unwrap()));
+
+    // b is now busy, so the frontend task goes to c
+    assert_eq!(task(19).borrow().get_who(&valid), Ok(Some("c".to_string())));
+    assert_eq!(task(19).borrow().get_allocation(),
+               Some(ChartPeriod::new(0, 11).unwrap()));
+
+    assert_eq!(task(22).borrow().get_who(&valid), Ok(None));
+    assert_eq!(task(22).borrow().get_notes(),
+               vec!["Nobody has the role tester".to_string()]);
+}
+
+#[test]
+fn role_commitment_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "4"));
+    f.add_line(Line::new_attribute_line("today", "2"));
+
+    f.add_line(Line::new_node_line(4, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("a", "2.. roles=backend"));
+    f.add_line(Line::new_attribute_line("b", "1.. roles=backend"));
+
+    f.add_line(Line::new_node_line(7, 1, "Tasks"));
+    f.add_line(Line::new_attribute_line("resource", "frontload"));
+    f.add_line(Line::new_node_line(9, 2, "Backend"));
+    f.add_line(Line::new_attribute_line("who", "any(backend)"));
+    f.add_line(Line::new_attribute_line("plan", "3"));
+    f.add_line(Line::new_attribute_line("C1.1", "1"));
+    f.add_line(Line::new_attribute_line("C1.3", "1"));
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+
+    // Only b was around in week 1, so b did the committed work and does
+    // the rest of the task too.
+    let root_ref = rc_root.borrow();
+    let valid: Vec<String> = vec!["a".to_string(), "b".to_string()];
+    let task = root_ref.get_node_at_line(9).unwrap().upgrade().unwrap();
+    assert_eq!(task.borrow().get_who(&valid), Ok(Some("b".to_string())));
+    assert_eq!(task.borrow().get_notes(), Vec::<String>::new());
+    let b = task.borrow().get_contribution("b").unwrap();
+    assert_eq!(b.count_range(0..20), 8.0);
+    assert_eq!(task.borrow().get_allocation(),
+               Some(ChartPeriod::new(20, 23).unwrap()));
+}
+
+#[test]
+fn external_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "4"));
+    f.add_line(Line::new_attribute_line("today", "1"));
+
+    f.add_line(Line::new_node_line(4, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("a", "1.."));
+
+    f.add_line(Line::new_node_line(6, 1, "[external]"));
+    f.add_line(Line::new_attribute_line("agency", "1"));
+    f.add_line(Line::new_attribute_line("outsource", "unlimited"));
+
+    f.add_line(Line::new_node_line(9, 1, "Tasks"));
+    f.add_line(Line::new_attribute_line("resource", "frontload"));
+    f.add_line(Line::new_attribute_line("non-managed", "true"));
+    f.add_line(Line::new_node_line(12, 2, "Agency 1"));
+    f.add_line(Line::new_attribute_line("who", "agency"));
+    f.add_line(Line::new_attribute_line("plan", "2"));
+    f.add_line(Line::new_attribute_line("start", "2"));
+    f.add_line(Line::new_node_line(16, 2, "Agency 2"));
+    f.add_line(Line::new_attribute_line("who", "agency"));
+    f.add_line(Line::new_attribute_line("plan", "1"));
+    f.add_line(Line::new_attribute_line("start", "2"));
+    f.add_line(Line::new_node_line(20, 2, "Outsourced"));
+    f.add_line(Line::new_attribute_line("who", "outsource"));
+    f.add_line(Line::new_attribute_line("plan", "3"));
+    f.add_line(Line::new_attribute_line("start", "2"));
+    f.add_line(Line::new_node_line(24, 1, "Serial"));
+    f.add_line(Line::new_attribute_line("schedule", "serial"));
+    f.add_line(Line::new_attribute_line("resource", "frontload"));
+    f.add_line(Line::new_attribute_line("non-managed", "true"));
+    f.add_line(Line::new_attribute_line("who", "a"));
+    f.add_line(Line::new_node_line(29, 2, "Build"));
+    f.add_line(Line::new_attribute_line("plan", "5"));
+    f.add_line(Line::new_node_line(31, 2, "Review"));
+    f.add_line(Line::new_attribute_line("who", "outsource"));
+    f.add_line(Line::new_attribute_line("plan", "6"));
+    f.add_line(Line::new_attribute_line("start", "1.3"));
+    f.add_line(Line::new_node_line(35, 2, "Deploy"));
+    f.add_line(Line::new_attribute_line("plan", "1"));
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+
+    let root_ref = rc_root.borrow();
+    let task = |line| root_ref.get_node_at_line(line).unwrap().upgrade().unwrap();
+
+    // The agency works on one task at a time
+    assert_eq!(task(12).borrow().get_allocation(),
+               Some(ChartPeriod::new(20, 27).unwrap()));
+    assert_eq!(task(16).borrow().get_allocation(),
+               Some(ChartPeriod::new(28, 31).unwrap()));
+
+    // There's no limit to outsourced work
+    assert_eq!(task(20).borrow().get_allocation(),
+               Some(ChartPeriod::new(20, 31).unwrap()));
+
+    // An outsourced task starts at its start time, even if the tasks
+    // before it in a serial schedule haven't finished, but the tasks
+    // after it still wait for it to finish.
+    assert_eq!(task(29).borrow().get_allocation(),
+               Some(ChartPeriod::new(0, 19).unwrap()));
+    assert_eq!(task(31).borrow().get_allocation(),
+               Some(ChartPeriod::new(8, 31).unwrap()));
+    assert!(task(31).borrow().get_notes().is_empty());
+    assert_eq!(task(35).borrow().get_allocation(),
+               Some(ChartPeriod::new(32, 35).unwrap()));
+
+    assert_eq!(root_ref.get_external(4).unwrap()["agency"].count(), 80.0);
+    assert_eq!(root_ref.get_people(4).unwrap()["a"].count(), 80.0);
+}
+
+#[test]
+fn part_time_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "2"));
+    f.add_line(Line::new_attribute_line("today", "1"));
+
+    f.add_line(Line::new_node_line(4, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("a", "1..1 capacity=0.1"));
+    f.add_line(Line::new_attribute_line("b", "1..1 capacity=0.1"));
+
+    f.add_line(Line::new_node_line(6, 1, "Task"));
+    f.add_line(Line::new_attribute_line("who", "a 70%, b 30%"));
+    f.add_line(Line::new_attribute_line("resource", "frontload"));
+    f.add_line(Line::new_attribute_line("non-managed", "true"));
+    f.add_line(Line::new_attribute_line("plan", "1"));
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+
+    // Each person can only do half a day in the week, and what's left of
+    // their shares is reported to the nearest quarter.
+    let root_ref = rc_root.borrow();
+    let task = root_ref.get_node_at_line(6).unwrap().upgrade().unwrap();
+    assert_eq!(task.borrow().get_notes(),
+               vec!["0.25 days did not fit".to_string()]);
+}
+
+#[test]
+fn deadline_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "4"));
+    f.add_line(Line::new_attribute_line("today", "1"));
+
+    f.add_line(Line::new_node_line(4, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("a", "1.."));
+
+    f.add_line(Line::new_node_line(6, 1, "Tasks"));
+    f.add_line(Line::new_attribute_line("who", "a"));
+    f.add_line(Line::new_attribute_line("resource", "frontload"));
+    f.add_line(Line::new_attribute_line("non-managed", "true"));
+    f.add_line(Line::new_attribute_line("deadline", "4"));
+    f.add_line(Line::new_node_line(11, 2, "Soft"));
+    f.add_line(Line::new_attribute_line("plan", "5"));
+    f.add_line(Line::new_attribute_line("deadline", "1.3"));
+    f.add_line(Line::new_node_line(14, 2, "Hard"));
+    f.add_line(Line::new_attribute_line("plan", "2"));
+    f.add_line(Line::new_attribute_line("start", "1.5"));
+    f.add_line(Line::new_attribute_line("latest-end", "2"));
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+
+    let root_ref = rc_root.borrow();
+    let task = |line| root_ref.get_node_at_line(line).unwrap().upgrade().unwrap();
+
+    assert_eq!(task(11).borrow().get_finish(), Some(ChartTime::new("1.5.4").unwrap()));
+    assert_eq!(task(11).borrow().get_notes(),
+               vec!["Misses deadline of 1.3 by 2.75 days".to_string()]);
+
+    // The hard task can only do a quarter before its latest end, so the
+    // rest is done afterwards.
+    assert_eq!(task(14).borrow().get_finish(), Some(ChartTime::new("2.2.4").unwrap()));
+    assert_eq!(task(14).borrow().get_notes(),
+               vec!["1.75 days did not fit".to_string(),
+                    "Misses start of 1.5 by 1 days".to_string(),
+                    "Misses latest-end of 2 by 1.75 days".to_string()]);
+    assert_eq!(task(6).borrow().get_finish(), Some(ChartTime::new("2.2.4").unwrap()));
+    assert!(task(6).borrow().get_notes().is_empty());
+
+    let summary = root_ref.get_deadline_summary();
+    assert_eq!(summary.len(), 4);
+    assert_eq!(summary[0].get_path(), "Tasks");
+    assert_eq!(summary[0].get_kind(), "deadline");
+    assert!(summary[0].get_missed().is_zero());
+    assert_eq!(summary[3].get_path(), "Tasks/Hard");
+    assert_eq!(summary[3].get_kind(), "latest-end");
+    assert_eq!(summary[3].get_missed(), Duration::new_days(1.75));
+}
+
+#[test]
+fn priority_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "4"));
+    f.add_line(Line::new_attribute_line("today", "1"));
+
+    f.add_line(Line::new_node_line(4, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("a", "1.."));
+
+    f.add_line(Line::new_node_line(6, 1, "Tasks"));
+    f.add_line(Line::new_attribute_line("who", "a"));
+    f.add_line(Line::new_attribute_line("resource", "frontload"));
+    f.add_line(Line::new_attribute_line("non-managed", "true"));
+    f.add_line(Line::new_node_line(10, 2, "P1"));
+    f.add_line(Line::new_node_line(11, 3, "X"));
+    f.add_line(Line::new_attribute_line("plan", "2"));
+    f.add_line(Line::new_node_line(13, 3, "W"));
+    f.add_line(Line::new_attribute_line("plan", "1"));
+    f.add_line(Line::new_attribute_line("priority", "10"));
+    f.add_line(Line::new_attribute_line("after", "Tasks/P1/X"));
+    f.add_line(Line::new_node_line(17, 2, "P2"));
+    f.add_line(Line::new_attribute_line("priority", "5"));
+    f.add_line(Line::new_node_line(19, 3, "Y"));
+    f.add_line(Line::new_attribute_line("plan", "1"));
+    f.add_line(Line::new_node_line(21, 3, "Z"));
+    f.add_line(Line::new_attribute_line("plan", "1"));
+    f.add_line(Line::new_attribute_line("priority", "-1"));
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+
+    let root_ref = rc_root.borrow();
+    let allocation = |line| {
+        root_ref.get_node_at_line(line).unwrap().upgrade().unwrap().borrow().get_allocation()
+    };
+
+    // Y has priority over X, and W over Z, but W must wait for X.
+    assert_eq!(allocation(19), Some(ChartPeriod::new(0, 3).unwrap()));
+    assert_eq!(allocation(11), Some(ChartPeriod::new(4, 11).unwrap()));
+    assert_eq!(allocation(13), Some(ChartPeriod::new(12, 15).unwrap()));
+    assert_eq!(allocation(21), Some(ChartPeriod::new(16, 19).unwrap()));
+}
+
+#[test]
+fn optimiser_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "4"));
+    f.add_line(Line::new_attribute_line("today", "1"));
+    f.add_line(Line::new_attribute_line("optimise", "10"));
+
+    f.add_line(Line::new_node_line(5, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("a", "1.."));
+
+    f.add_line(Line::new_node_line(7, 1, "Tasks"));
+    f.add_line(Line::new_attribute_line("who", "a"));
+    f.add_line(Line::new_attribute_line("resource", "frontload"));
+    f.add_line(Line::new_attribute_line("non-managed", "true"));
+    f.add_line(Line::new_node_line(11, 2, "Big"));
+    f.add_line(Line::new_attribute_line("plan", "5"));
+    f.add_line(Line::new_node_line(13, 2, "Small"));
+    f.add_line(Line::new_attribute_line("plan", "1"));
+    f.add_line(Line::new_attribute_line("deadline", "1.2"));
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+
+    let root_ref = rc_root.borrow();
+    let task = |line| root_ref.get_node_at_line(line).unwrap().upgrade().unwrap();
+
+    // Doing the small task first means that it meets its deadline.
+    assert_eq!(task(13).borrow().get_allocation(),
+               Some(ChartPeriod::new(0, 3).unwrap()));
+    assert_eq!(task(11).borrow().get_allocation(),
+               Some(ChartPeriod::new(4, 23).unwrap()));
+    assert!(task(13).borrow().get_notes().is_empty());
+
+    let report = root_ref.get_optimiser_report().unwrap();
+    assert_eq!(report.get_iterations(), 3);
+    assert_eq!(report.get_greedy_lateness(), 4.75);
+    assert_eq!(report.get_lateness(), 0.0);
+    assert_eq!(report.get_changes(),
+               &vec!["Allocated Tasks/Small before Tasks/Big".to_string()]);
+    assert_eq!(report.get_deadlines(),
+               &vec![("Tasks/Small".to_string(),
+                      "deadline".to_string(),
+                      Duration::new_days(4.75),
+                      Duration::new_days(0.0))]);
+}
+
+#[test]
+fn management_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "1"));
+    f.add_line(Line::new_attribute_line("today", "1"));
+
+    f.add_line(Line::new_node_line(4, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("a", "1.."));
+    f.add_line(Line::new_attribute_line("b", "1.."));
+    f.add_line(Line::new_attribute_line("c", "1.."));
+    f.add_line(Line::new_attribute_line("d", "1.."));
+
+    f.add_line(Line::new_node_line(9, 1, "Management"));
+    f.add_line(Line::new_node_line(11, 2, "Lead 1"));
+    f.add_line(Line::new_attribute_line("who", "a"));
+    f.add_line(Line::new_attribute_line("resource", "management"));
+    f.add_line(Line::new_node_line(14, 2, "Lead 2"));
+    f.add_line(Line::new_attribute_line("who", "b"));
+    f.add_line(Line::new_attribute_line("resource", "management"));
+    f.add_line(Line::new_attribute_line("manages", "d,zz"));
+    f.add_line(Line::new_attribute_line("ratio", "0.5"));
+
+    f.add_line(Line::new_node_line(20, 1, "Tasks"));
+    f.add_line(Line::new_attribute_line("resource", "frontload"));
+    f.add_line(Line::new_node_line(22, 2, "A"));
+    f.add_line(Line::new_attribute_line("who", "a"));
+    f.add_line(Line::new_attribute_line("plan", "1"));
+    f.add_line(Line::new_node_line(25, 2, "B"));
+    f.add_line(Line::new_attribute_line("who", "b"));
+    f.add_line(Line::new_attribute_line("plan", "1"));
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+
+    let root_ref = rc_root.borrow();
+    let task = |line| root_ref.get_node_at_line(line).unwrap().upgrade().unwrap();
+
+    // a manages everyone that b doesn't, including b, so spends 20% of
+    // b's and c's time.  b spends half of d's time.
+    assert_eq!(task(22).borrow().get_allocation(),
+               Some(ChartPeriod::new(8, 11).unwrap()));
+    assert_eq!(task(25).borrow().get_allocation(),
+               Some(ChartPeriod::new(10, 13).unwrap()));
+
+    assert!(task(11).borrow().get_notes().is_empty());
+    assert_eq!(task(14).borrow().get_notes(),
+               vec!["Unknown person zz in manages".to_string()]);
+}
+
+#[test]
+fn part_time_management_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "1"));
+    f.add_line(Line::new_attribute_line("today", "1"));
+
+    f.add_line(Line::new_node_line(4, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("m", "1.. pattern=mon-tue"));
+    f.add_line(Line::new_attribute_line("x", "1.."));
+    f.add_line(Line::new_attribute_line("y", "1.."));
+
+    f.add_line(Line::new_node_line(8, 1, "Management"));
+    f.add_line(Line::new_node_line(9, 2, "Lead 1"));
+    f.add_line(Line::new_attribute_line("who", "m"));
+    f.add_line(Line::new_attribute_line("resource", "management"));
+    f.add_line(Line::new_attribute_line("manages", "x"));
+    f.add_line(Line::new_attribute_line("ratio", "1"));
+    f.add_line(Line::new_node_line(14, 2, "Lead 2"));
+    f.add_line(Line::new_attribute_line("who", "y"));
+    f.add_line(Line::new_attribute_line("resource", "management"));
+    f.add_line(Line::new_attribute_line("manages", "x"));
+
+    f.add_line(Line::new_node_line(18, 1, "Tasks"));
+    f.add_line(Line::new_attribute_line("resource", "frontload"));
+    f.add_line(Line::new_node_line(20, 2, "Y"));
+    f.add_line(Line::new_attribute_line("who", "y"));
+    f.add_line(Line::new_attribute_line("plan", "1"));
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+
+    let root_ref = rc_root.borrow();
+    let task = |line| root_ref.get_node_at_line(line).unwrap().upgrade().unwrap();
+
+    // m only works two days a week, so that's all that m can spend
+    // managing x.
+    assert!(task(9).borrow().get_notes().is_empty());
+
+    // x is only managed by the first node to list them, so y is free.
+    assert_eq!(task(14).borrow().get_notes(),
+               vec!["x is already managed by Management/Lead 1".to_string()]);
+    assert_eq!(task(20).borrow().get_allocation(),
+               Some(ChartPeriod::new(0, 3).unwrap()));
+}
+
+#[test]
+fn scenario_test() {
+
+    fn generate(scenario: Option<&mut ConfigLines>) -> Rc<RefCell<ConfigNode>> {
+        let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+        let mut f = ConfigLines::new();
+        f.add_line(Line::new_node_line(1, 1, "[chart]"));
+        f.add_line(Line::new_attribute_line("weeks", "2"));
+        f.add_line(Line::new_attribute_line("today", "1"));
+
+        f.add_line(Line::new_node_line(4, 1, "[people]"));
+        f.add_line(Line::new_attribute_line("a", "1.."));
+        f.add_line(Line::new_attribute_line("b", "1.."));
+
+        f.add_line(Line::new_node_line(7, 1, "Tasks"));
+        f.add_line(Line::new_attribute_line("resource", "frontload"));
+        f.add_line(Line::new_attribute_line("non-managed", "true"));
+        f.add_line(Line::new_node_line(10, 2, "A"));
+        f.add_line(Line::new_attribute_line("who", "a"));
+        f.add_line(Line::new_attribute_line("plan", "5"));
+        f.add_line(Line::new_node_line(13, 2, "B"));
+        f.add_line(Line::new_attribute_line("who", "b"));
+        f.add_line(Line::new_attribute_line("plan", "2"));
+
+        {
+            let mut root = rc_root.borrow_mut();
+            root.consume_config(Some(&rc_root), &mut f).unwrap();
+            match scenario {
+                Some(s) => root.apply_scenario(s).unwrap(),
+                None => {}
+            };
+            root.fill_in_gantt().unwrap();
+        }
+        rc_root
+    }
+
+    let baseline = generate(None).borrow().get_scenario_summary().unwrap();
+    assert_eq!(baseline.get_finishes(),
+               &vec![("Tasks".to_string(), Some(ChartTime::new_from_quarter(19)))]);
+    assert_eq!(baseline.get_gains(),
+               &vec![("Tasks".to_string(), Duration::new_days(0.0))]);
+    assert_eq!(baseline.get_utilisation(),
+               &vec![("a".to_string(), 50.0), ("b".to_string(), 20.0)]);
+
+    // a starts a week late, and B takes twice as long.
+    let mut s = ConfigLines::new();
+    s.add_line(Line::new_node_line(1, 1, "[people]"));
+    s.add_line(Line::new_attribute_line("a", "2.."));
+    s.add_line(Line::new_node_line(3, 1, "Tasks/B"));
+    s.add_line(Line::new_attribute_line("plan", "4"));
+    s.add_line(Line::new_attribute_line("plna", "6"));
+    let scenario = generate(Some(&mut s)).borrow().get_scenario_summary().unwrap();
+    assert_eq!(scenario.get_finishes(),
+               &vec![("Tasks".to_string(), Some(ChartTime::new_from_quarter(39)))]);
+    assert_eq!(scenario.get_utilisation(),
+               &vec![("a".to_string(), 100.0), ("b".to_string(), 40.0)]);
+
+    // Attributes that the scenario adds, rather than replaces, are noted
+    assert!(baseline.get_notes().is_empty());
+    assert_eq!(scenario.get_notes(),
+               &vec!["Scenario adds plna to Tasks/B, which doesn't have it".to_string()]);
+
+    // Scenarios can only change nodes that exist.
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+    let mut s = ConfigLines::new();
+    s.add_line(Line::new_node_line(1, 1, "Tasks/C"));
+    s.add_line(Line::new_attribute_line("plan", "4"));
+    assert_eq!(rc_root.borrow().apply_scenario(&mut s),
+               Err("Unknown node Tasks/C at line 1 of scenario".to_string()));
+}
+
+#[test]
+fn estimate_test() {
+    let two_weeks = Duration::new_days(10.0f32);
+
+    let e = Estimate::new_from_string("8..12", &two_weeks).unwrap();
+    assert_eq!(e.get_expected(), Duration::new_days(10.0));
+    assert_eq!(e.get_quantile(0.0), Duration::new_days(8.0));
+    assert_eq!(e.get_quantile(0.25), Duration::new_days(9.0));
+
+    let e = Estimate::new_from_string("6/9/15", &two_weeks).unwrap();
+    assert_eq!(e.get_expected(), Duration::new_days(10.0));
+    assert_eq!(e.get_quantile(0.0), Duration::new_days(6.0));
+    assert_eq!(e.get_quantile(1.0 / 3.0), Duration::new_days(9.0));
+    assert_eq!(e.get_quantile(1.0), Duration::new_days(15.0));
+
+    let e = Estimate::new_from_string("26..52pcy", &two_weeks).unwrap();
+    assert_eq!(e.get_expected(), Duration::new_days(1.5));
+    assert_eq!(Duration::new_from_string("4/5/9", &two_weeks).unwrap(),
+               Duration::new_days(6.0));
+
+    assert!(Estimate::new_from_string("12..8", &two_weeks).is_err());
+    assert!(Estimate::new_from_string("6/9", &two_weeks).is_err());
+    assert!(Estimate::new_from_string("6..9/15", &two_weeks).is_err());
+
+    // Random numbers repeat for the same seed.
+    let mut r1 = Random::new(1);
+    let mut r2 = Random::new(1);
+    for _ in 0..10 {
+        let f = r1.next_fraction();
+        assert!(f >= 0.0 && f < 1.0);
+        assert_eq!(f, r2.next_fraction());
+    }
+    assert!(Random::new(2).next_u64() != Random::new(1).next_u64());
+}
+
+#[test]
+fn simulation_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "4"));
+    f.add_line(Line::new_attribute_line("today", "1"));
+    f.add_line(Line::new_attribute_line("simulations", "100"));
+
+    f.add_line(Line::new_node_line(5, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("a", "1.."));
+
+    f.add_line(Line::new_node_line(7, 1, "Tasks"));
+    f.add_line(Line::new_attribute_line("who", "a"));
+    f.add_line(Line::new_attribute_line("resource", "frontload"));
+    f.add_line(Line::new_attribute_line("non-managed", "true"));
+    f.add_line(Line::new_attribute_line("budget", "10"));
+    f.add_line(Line::new_node_line(12, 2, "A"));
+    f.add_line(Line::new_attribute_line("plan", "4..8"));
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+
+    let root_ref = rc_root.borrow();
+    let task = |line| root_ref.get_node_at_line(line).unwrap().upgrade().unwrap();
+
+    // The chart itself uses the mean of the estimate.
+    assert_eq!(task(12).borrow().get_allocation(),
+               Some(ChartPeriod::new(0, 23).unwrap()));
+
+    let report = root_ref.get_simulation_report().unwrap();
+    assert_eq!(report.get_runs(), 100);
+    assert_eq!(report.get_seed(), 1);
+
+    let finishes = report.get_finishes();
+    assert_eq!(finishes.len(), 2);
+    assert_eq!(finishes[1].0, "Tasks/A");
+    let quarters: Vec<u32> = finishes[1].1.iter().map(|t| t.get_quarter()).collect();
+    assert!(quarters[0] > 15 && quarters[2] <= 31);
+    assert!(quarters[0] <= quarters[1] && quarters[1] <= quarters[2]);
+
+    let costs = report.get_costs();
+    assert_eq!(costs.len(), 1);
+    assert_eq!(costs[0].1, Duration::new_days(10.0));
+    assert_eq!(costs[0].2[1], Duration::new_quarters(quarters[1] as i32 + 1));
+
+    // The same seed gives the same results.
+    root_ref.simulate(100, 1).unwrap();
+    assert_eq!(root_ref.get_simulation_report(), Some(report));
+}
+
+#[test]
+fn simulated_split_plan_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "4"));
+    f.add_line(Line::new_attribute_line("today", "1"));
+    f.add_line(Line::new_attribute_line("simulations", "20"));
+
+    f.add_line(Line::new_node_line(5, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("a", "1.."));
+
+    f.add_line(Line::new_node_line(7, 1, "Tasks"));
+    f.add_line(Line::new_attribute_line("who", "a"));
+    f.add_line(Line::new_attribute_line("resource", "frontload"));
+    f.add_line(Line::new_attribute_line("non-managed", "true"));
+    f.add_line(Line::new_attribute_line("plan", "8..12"));
+    f.add_line(Line::new_attribute_line("split-plan", "true"));
+    f.add_line(Line::new_node_line(13, 2, "A"));
+    f.add_line(Line::new_node_line(14, 2, "B"));
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+
+    let root_ref = rc_root.borrow();
+    let task = |line| root_ref.get_node_at_line(line).unwrap().upgrade().unwrap();
+    let when = ChartTime::new("5").unwrap();
+    let four_weeks = Duration::new_days(20.0);
+
+    // Each run splits that run's plan, so the children's finish times
+    // vary, and the chart itself splits the mean of the estimate.
+    let finishes = root_ref.get_simulation_report().unwrap().get_finishes().clone();
+    assert_eq!(finishes[2].0, "Tasks/B");
+    assert!(finishes[2].1[0] < finishes[2].1[2]);
+    assert_eq!(task(13).borrow().get_plan(&when, &four_weeks).unwrap(),
+               Some(Duration::new_days(5.0)));
+    assert_eq!(task(14).borrow().get_allocation(),
+               Some(ChartPeriod::new(20, 39).unwrap()));
+}
+
+#[test]
+fn recurrence_test() {
+    let r = Recurrence::new_from_commitment("2..3/fri.4", "0.25").unwrap();
+    assert_eq!(r.get_commitments(10),
+               vec![(ChartTime::new("2.5.4").unwrap(), Duration::new_quarters(1)),
+                    (ChartTime::new("3.5.4").unwrap(), Duration::new_quarters(1))]);
+    let r = Recurrence::new_from_commitment("9../2", "1").unwrap();
+    assert_eq!(r.get_commitments(10),
+               vec![(ChartTime::new("9.2.1").unwrap(), Duration::new_days(1.0)),
+                    (ChartTime::new("10.2.1").unwrap(), Duration::new_days(1.0))]);
+    let r = Recurrence::new_from_every("mon x 0.5").unwrap();
+    assert_eq!(r.get_commitments(2).len(), 2);
+
+    assert!(Recurrence::new_from_commitment("3..2/fri", "1").is_err());
+    assert!(Recurrence::new_from_commitment("3/sat", "1").is_err());
+    assert!(Recurrence::new_from_commitment("3/fri.5", "1").is_err());
+    assert!(Recurrence::new_from_commitment("3/fri", "1d").is_err());
+    assert!(Recurrence::new_from_every("fri.4 0.25").is_err());
+
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "3"));
+    f.add_line(Line::new_attribute_line("today", "1"));
+
+    f.add_line(Line::new_node_line(4, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("a", "1..2"));
+
+    f.add_line(Line::new_node_line(6, 1, "Meetings"));
+    f.add_line(Line::new_attribute_line("who", "a"));
+    f.add_line(Line::new_attribute_line("non-managed", "true"));
+    f.add_line(Line::new_node_line(9, 2, "Team"));
+    f.add_line(Line::new_attribute_line("every", "fri.4 x 0.25"));
+    f.add_line(Line::new_attribute_line("C2..3/mon", "0.5"));
+    f.add_line(Line::new_node_line(12, 2, "Typo"));
+    f.add_line(Line::new_attribute_line("C2/sat", "1"));
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.fill_in_gantt().unwrap();
+    }
+
+    let root_ref = rc_root.borrow();
+    let task = |line| root_ref.get_node_at_line(line).unwrap().upgrade().unwrap();
+
+    // a isn't available in week 3, so there are no meetings that week.
+    let row = task(9).borrow().get_contribution("a").unwrap();
+    assert_eq!(row.count(), 4.0);
+    assert_eq!(row.get(19), 1.0);
+    assert_eq!(row.get(20), 1.0);
+    assert_eq!(row.get(21), 1.0);
+    assert_eq!(row.get(39), 1.0);
+
+    assert!(task(12)
+                .borrow()
+                .get_notes()
+                .contains(&"Problem in node at line 12: Invalid day sat in recurring \
+                            commitment C2/sat"
+                                   .to_string()));
+}
+
+#[test]
+fn commitment_check_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "Task"));
+    f.add_line(Line::new_attribute_line("C2", "1"));
+    f.add_line(Line::new_attribute_line("C2.6", "1"));
+    f.add_line(Line::new_attribute_line("C3", "1"));
+    f.add_line(Line::new_attribute_line("C3.1", "0.5"));
+    f.add_line(Line::new_attribute_line("C4", "1d"));
+    f.add_line(Line::new_attribute_line("C5", "-1"));
+    f.add_line(Line::new_attribute_line("C6..8", "1"));
+    f.add_line(Line::new_attribute_line("Cost", "high"));
+    f.add_line(Line::new_attribute_line("every", "fri.4 x 0.25, sat x 1"));
+    rc_root.borrow_mut().consume_config(Some(&rc_root), &mut f).unwrap();
+
+    let root_ref = rc_root.borrow();
+    let task = root_ref.get_node_at_line(1).unwrap().upgrade().unwrap();
+    let task = task.borrow();
+
+    // Only the valid commitments are used.
+    assert_eq!(task.get_commitments(),
+               vec![(ChartTime::new("2").unwrap(), Duration::new_days(1.0)),
+                    (ChartTime::new("3").unwrap(), Duration::new_days(1.0))]);
+    assert_eq!(task.get_recurring_commitments(2).len(), 2);
+
+    // Everything else that looks like a commitment is reported.
+    let problems: Vec<String> = task.check_commitments()
+        .iter()
+        .map(|p| p.replace("Problem in node at line 1: ", ""))
+        .collect();
+    assert_eq!(problems,
+               vec!["Invalid commitment C2.6: Failed to parse day from time string:2.6, \
+                     value is out of range"
+                            .to_string(),
+                    "Commitments C3 and C3.1 start at the same time".to_string(),
+                    "Invalid commitment C4: 1d is not a positive number of days".to_string(),
+                    "Invalid commitment C5: -1 is not a positive number of days".to_string(),
+                    "Recurring commitment C6..8 must be C<weeks>/<day>".to_string(),
+                    "Invalid day sat in recurring commitment sat x 1".to_string()]);
+}
+
+#[test]
+fn timesheet_test() {
+    let rc_root = Rc::new(RefCell::new(ConfigNode::new("root", 0, 0, 0)));
+
+    // Set up config file
+    let mut f = ConfigLines::new();
+    f.add_line(Line::new_node_line(1, 1, "[chart]"));
+    f.add_line(Line::new_attribute_line("weeks", "2"));
+    f.add_line(Line::new_attribute_line("today", "2"));
+
+    f.add_line(Line::new_node_line(4, 1, "[people]"));
+    f.add_line(Line::new_attribute_line("a", "1.."));
+    f.add_line(Line::new_attribute_line("b", "1.."));
+
+    f.add_line(Line::new_node_line(7, 1, "Tasks"));
+    f.add_line(Line::new_attribute_line("resource", "frontload"));
+    f.add_line(Line::new_attribute_line("non-managed", "true"));
+    f.add_line(Line::new_node_line(10, 2, "A"));
+    f.add_line(Line::new_attribute_line("who", "a"));
+    f.add_line(Line::new_attribute_line("plan", "3"));
+
+    let timesheet = Timesheet::new("person,time,node,days\n\
+                                    a,1.1,Tasks/A,0.5\n\
+                                    a,1.1,Tasks/A,0.5\n\
+                                    a,1.1,Tasks/A,0.5\n\
+                                    b,1.2,Tasks/A,1\n\
+                                    zz,1.1,Tasks/A,1\n\
+                                    a,1.1,Tasks/Z,1\n\
+                                    a,1.1,Tasks,1\n\
+                                    a,1.1\n\
+                                    a,1.3,Tasks/A,x\n");
+    assert_eq!(timesheet.get_rows().len(), 7);
+
+    {
+        let mut root = rc_root.borrow_mut();
+        root.consume_config(Some(&rc_root), &mut f).unwrap();
+        root.set_timesheet(timesheet);
+        root.fill_in_gantt().unwrap();
+    }
+
+    let root_ref = rc_root.borrow();
+    let task = |line| root_ref.get_node_at_line(line).unwrap().upgrade().unwrap();
+
+    // Work on the same day is fitted in, until the day is full.
+    let a = task(10).borrow().get_contribution("a").unwrap();
+    assert_eq!(a.count_range(0..4), 4.0);
+    assert_eq!(a.count_range(4..20), 0.0);
+    let b = task(10).borrow().get_contribution("b").unwrap();
+    assert_eq!(b.count_range(4..8), 4.0);
+    assert_eq!(b.count(), 4.0);
+    assert!(task(10)
+                .borrow()
+                .get_notes()
+                .contains(&"Timesheet line 4 overbooks a by 0.5 days".to_string()));
+
+    assert_eq!(root_ref.get_notes()[..5].to_vec(),
+               vec!["Timesheet line 9 must be <person>,<time>,<node>,<days>".to_string(),
+                    "Timesheet line 10: x is not a positive number of days".to_string(),
+                    "Timesheet line 6: unknown person zz".to_string(),
+                    "Timesheet line 7: unknown node Tasks/Z".to_string(),
+                    "Timesheet line 8: Tasks isn't a task".to_string()]);
+}
@@ -1,5 +1,7 @@
+use super::date::*;
 use super::duration::*;
 use std::cmp::Ordering;
+use std::fmt;
 use std::str::FromStr;
 
 #[derive(Debug, Eq, Copy, Clone)]
@@ -27,6 +29,21 @@ impl PartialEq for ChartTime {
     }
 }
 
+impl fmt::Display for ChartTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{}", self.week));
+        match self.day {
+            Some(day) => try!(write!(f, ".{}", day)),
+            None => {}
+        };
+        match self.quarter {
+            Some(quarter) => try!(write!(f, ".{}", quarter)),
+            None => {}
+        };
+        Ok(())
+    }
+}
+
 impl FromStr for ChartTime {
     type Err = String;
 
@@ -47,7 +64,50 @@ impl ChartTime {
         }
     }
 
+    /// Get the time of the day with a given date, in a chart that starts
+    /// on the Monday with the given start date.
+    pub fn new_from_date(date: &ChartDate, start: Option<ChartDate>) -> Result<ChartTime, String> {
+        let start = match start {
+            Some(start) => start,
+            None => {
+                return Err(format!("Cannot use date {} without a start-date in [chart]", date));
+            }
+        };
+
+        let days = date.get_days() - start.get_days();
+        if days < 0 {
+            return Err(format!("Date {} is before the start of the chart", date));
+        }
+        if date.get_weekday() > 4 {
+            return Err(format!("Date {} is not a weekday", date));
+        }
+        Ok(ChartTime {
+               week: (days / 7) as u32 + 1,
+               day: Some(date.get_weekday() + 1),
+               quarter: None,
+           })
+    }
+
+    /// Get the date of this time, in a chart that starts on the Monday
+    /// with the given start date.
+    pub fn get_date(&self, start: &ChartDate) -> ChartDate {
+        start.add_days((self.week as i64 - 1) * 7 + self.day.unwrap_or(1) as i64 - 1)
+    }
+
+    /// Parse a time, as <week>.<day>.<quarter>.  Times that can be dates
+    /// are parsed with new_with_start.
     pub fn new(desc: &str) -> Result<ChartTime, String> {
+        ChartTime::new_with_start(desc, None)
+    }
+
+    /// Parse a time, either as <week>.<day>.<quarter>, or as a date
+    /// with an optional quarter, eg "2026-10-07.3".  Dates can only be
+    /// used if the chart has a start date, which must be a Monday.
+    pub fn new_with_start(desc: &str, start: Option<ChartDate>) -> Result<ChartTime, String> {
+        if desc.contains('-') {
+            return ChartTime::new_with_date(desc, start);
+        }
+
         let v: Vec<&str> = desc.split('.').collect();
         if v.len() > 3 {
 
@@ -119,6 +179,28 @@ impl ChartTime {
            })
     }
 
+    fn new_with_date(desc: &str, start: Option<ChartDate>) -> Result<ChartTime, String> {
+        let v: Vec<&str> = desc.split('.').collect();
+        if v.len() > 2 {
+            return Err(format!("Too many parts in time string: {}", desc));
+        }
+
+        let date = try!(ChartDate::new(v[0]));
+        let mut time = try!(ChartTime::new_from_date(&date, start));
+        if v.len() > 1 {
+            match v[1].parse::<u32>() {
+                Ok(num) if num >= 1 && num <= 4 => {
+                    time.quarter = Some(num);
+                }
+                _ => {
+                    return Err(format!("Failed to parse quarter from time string:{}", desc));
+                }
+            }
+        }
+
+        Ok(time)
+    }
+
     /// Get the first (0-based) quarter associated with this time
     pub fn get_quarter(&self) -> u32 {
         let mut q = (self.week - 1) * 20;
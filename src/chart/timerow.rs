@@ -1,28 +1,56 @@
-use std;
 use std::fmt;
+use std::ops::Range;
+use super::date::*;
 use super::time::*;
+use super::period::*;
 
 
+/// Amounts smaller than this are treated as zero, to avoid problems with
+/// floating-point inaccuracies.
+const EPSILON: f32 = 0.0001;
+
+/// A run of cells that all have the same capacity
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Span {
+    /// First cell in the run
+    first: u32,
+
+    /// The cell after the last cell in the run
+    end: u32,
+
+    /// Capacity of each cell in the run
+    value: f32,
+}
+
 /// The time cells for a single Gantt row, split into 1/4 day chunks.
-#[derive(Debug)]
+///
+/// Each cell holds an amount of capacity, in units of one person for one
+/// quarter.  A person's row usually holds 1.0 in each quarter that they
+/// are available, but can hold less (part time) or more (overtime).  A
+/// task's row holds the amount of each quarter spent on the task.
+///
+/// The cells are held as runs of cells with the same capacity, so that
+/// long charts can be handled efficiently.
+#[derive(Debug, Clone)]
 pub struct ChartTimeRow {
-    /// Cells, as a bit field
-    cells: Vec<u8>,
+    /// Runs of cells with capacity, in order.  The runs don't overlap,
+    /// and adjacent runs have different capacities.
+    spans: Vec<Span>,
 }
 
 impl fmt::Display for ChartTimeRow {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 
         let mut output = String::new();
-        for cell in &self.cells {
-            let mut cell_copy = *cell;
-            for _ in 0..8 {
-                if cell_copy & 0x01 == 0x01 {
-                    output = output + "o";
-                } else {
-                    output = output + "_";
-                }
-                cell_copy >>= 1;
+        let end = self.spans.last().map_or(0, |s| s.end);
+        for cell in 0..end {
+            let value = self.get(cell);
+            if value >= 1.0 - EPSILON {
+                output = output + "o";
+            } else if value > EPSILON {
+                output = output + ".";
+            } else {
+                output = output + "_";
             }
         }
 
@@ -33,7 +61,7 @@ impl fmt::Display for ChartTimeRow {
 impl ChartTimeRow {
     /// Create new row with all cells unallocated
     pub fn new() -> ChartTimeRow {
-        ChartTimeRow { cells: Vec::new() }
+        ChartTimeRow { spans: Vec::new() }
     }
 
     /// Return a string describing the weekly numbers
@@ -41,16 +69,17 @@ impl ChartTimeRow {
 
         let mut output = String::new();
         for count in self.get_weekly_numbers(weeks) {
-            match count {
-                0 => output.push_str("   "),
-                _ => output.push_str(&format!("{: >3}", count)),
-            };
+            if count.abs() < EPSILON {
+                output.push_str("   ");
+            } else {
+                output.push_str(&format!("{: >3}", count));
+            }
         }
         output
     }
 
     /// Return a vector of the weekly numbers
-    pub fn get_weekly_numbers(&self, weeks: u32) -> Vec<u32> {
+    pub fn get_weekly_numbers(&self, weeks: u32) -> Vec<f32> {
 
         let mut output = Vec::new();
         for week in 0..weeks {
@@ -63,8 +92,12 @@ impl ChartTimeRow {
     /// specified range.
     ///
     /// The range takes the form <start>..[<end>], where
-    /// start and end are chart times.
-    pub fn new_populate_range(range: &str, weeks: u32) -> Result<ChartTimeRow, String> {
+    /// start and end are chart times, or dates if the chart has a
+    /// start date.
+    pub fn new_populate_range(range: &str,
+                              weeks: u32,
+                              start_date: Option<ChartDate>)
+                              -> Result<ChartTimeRow, String> {
 
         let v: Vec<&str> = range.split("..").collect();
         if v.len() > 2 {
@@ -73,12 +106,13 @@ impl ChartTimeRow {
         if v.len() == 0 {
             return Err(format!("Not enough parts in range {}", range));
         }
-        let start =
-            try!(ChartTime::new(v[0]).map_err(|e| format!("Invalid range start {}, {}", v[0], e)));
+        let start = try!(ChartTime::new_with_start(v[0], start_date)
+            .map_err(|e| format!("Invalid range start {}, {}", v[0], e)));
         let end = if v.len() == 1 || v[1].len() == 0 {
             try!(ChartTime::new(&format!("{}", weeks)))
         } else {
-            try!(ChartTime::new(v[1]).map_err(|e| format!("Invalid range end {}, {}", v[1], e)))
+            try!(ChartTime::new_with_start(v[1], start_date)
+                .map_err(|e| format!("Invalid range end {}, {}", v[1], e)))
         };
 
         if try!(ChartTime::new(&format!("{}", weeks))) < end {
@@ -92,213 +126,490 @@ impl ChartTimeRow {
         Ok(ctr)
     }
 
-    /// Set a specific cell
-    pub fn set(&mut self, cell: u32) {
-        let byte = (cell / 8) as usize;
-        let bit = cell % 8;
-        let test = 0x01 << bit;
-
-        while self.cells.len() <= byte {
-            self.cells.push(0);
+    /// Get the index of the first run that ends after a cell
+    fn find_span(&self, cell: u32) -> usize {
+        let mut lo = 0;
+        let mut hi = self.spans.len();
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if self.spans[mid].end <= cell {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
         }
+        lo
+    }
 
-        self.cells[byte] |= test;
+    /// Get the runs that overlap a range of cells
+    fn spans_in(&self, range: &Range<u32>) -> &[Span] {
+        let lo = self.find_span(range.start);
+        let mut hi = lo;
+        while hi < self.spans.len() && self.spans[hi].first < range.end {
+            hi += 1;
+        }
+        &self.spans[lo..hi]
     }
 
-    /// Unset a specific cell
-    pub fn unset(&mut self, cell: u32) {
-        let byte = (cell / 8) as usize;
-        let bit = cell % 8;
-        let test = 0x01 << bit;
+    /// Set the capacity of all of the cells from first up to end.
+    fn set_span(&mut self, first: u32, end: u32, value: f32) {
+        if first >= end {
+            return;
+        }
 
-        if self.cells.len() > byte {
-            self.cells[byte] &= !test;
+        // Find the runs that overlap, and keep the parts of them
+        // outside the new run.
+        let lo = self.find_span(first);
+        let mut hi = lo;
+        while hi < self.spans.len() && self.spans[hi].first < end {
+            hi += 1;
+        }
+        let mut replacement = Vec::new();
+        if lo < hi && self.spans[lo].first < first {
+            replacement.push(Span { end: first, ..self.spans[lo] });
+        }
+        if value > EPSILON {
+            replacement.push(Span {
+                                 first: first,
+                                 end: end,
+                                 value: value,
+                             });
+        }
+        if lo < hi && self.spans[hi - 1].end > end {
+            replacement.push(Span { first: end, ..self.spans[hi - 1] });
         }
 
+        // Replace the runs, then merge with any matching neighbours.
+        let count = replacement.len();
+        let _ = self.spans.splice(lo..hi, replacement);
+        let mut ix = if lo > 0 { lo - 1 } else { 0 };
+        while ix + 1 < self.spans.len() && ix <= lo + count {
+            if self.spans[ix].end == self.spans[ix + 1].first &&
+               self.spans[ix].value == self.spans[ix + 1].value {
+                self.spans[ix].end = self.spans[ix + 1].end;
+                self.spans.remove(ix + 1);
+            } else {
+                ix += 1;
+            }
+        }
     }
 
-    /// Test whether a specific cell is set
-    pub fn is_set(&self, cell: u32) -> bool {
-        let byte = (cell / 8) as usize;
-        let bit = cell % 8;
-        let test = 0x01 << bit;
+    /// Get the part of a range, starting at a given cell, where the
+    /// capacity of this row and another row are both constant.  Returns
+    /// the end cell of the part, and the capacity of each row.
+    fn segment_from(&self, other: &ChartTimeRow, cell: u32, end: u32) -> (u32, f32, f32) {
+        let mut seg_end = end;
+        for row in &[self, other] {
+            match row.spans.get(row.find_span(cell)) {
+                Some(span) if span.first > cell && span.first < seg_end => seg_end = span.first,
+                Some(span) if span.first <= cell && span.end < seg_end => seg_end = span.end,
+                _ => {}
+            };
+        }
+        (seg_end, self.get(cell), other.get(cell))
+    }
 
-        if self.cells.len() < byte + 1 {
-            return false;
+    /// Get the part of a range, ending just before a given cell, where the
+    /// capacity of this row and another row are both constant.  Returns
+    /// the first cell of the part, and the capacity of each row.
+    fn segment_before(&self, other: &ChartTimeRow, cell: u32, start: u32) -> (u32, f32, f32) {
+        let last = cell - 1;
+        let mut seg_first = start;
+        for row in &[self, other] {
+            let ix = row.find_span(last);
+            match row.spans.get(ix) {
+                Some(span) if span.first <= last => {
+                    if span.first > seg_first {
+                        seg_first = span.first;
+                    }
+                }
+                _ => {
+                    // In a gap, which starts at the end of the previous run.
+                    if ix > 0 && row.spans[ix - 1].end > seg_first {
+                        seg_first = row.spans[ix - 1].end;
+                    }
+                }
+            };
         }
+        (seg_first, self.get(last), other.get(last))
+    }
 
-        self.cells[byte] & test == test
+    /// Split a range into the parts where the capacity of this row and
+    /// another row are both constant.  Returns the first and end cells of
+    /// each part, and the capacity of each row.
+    fn segments(&self, other: &ChartTimeRow, range: Range<u32>) -> Vec<(u32, u32, f32, f32)> {
+
+        let mut segments = Vec::new();
+        let mut cell = range.start;
+        while cell < range.end {
+            let (end, value, other_value) = self.segment_from(other, cell, range.end);
+            segments.push((cell, end, value, other_value));
+            cell = end;
+        }
+        segments
     }
 
-    /// Set a range of cells
-    pub fn set_range<'a, I>(&mut self, range: I)
-        where I: Iterator<Item = u32>
+    /// Combine this row with another row, cell by cell.
+    fn combine<F>(&mut self, other: &ChartTimeRow, f: F)
+        where F: Fn(f32, f32) -> f32
     {
+        let end = match (self.spans.last(), other.spans.last()) {
+            (Some(a), Some(b)) => if a.end > b.end { a.end } else { b.end },
+            (Some(a), None) => a.end,
+            (None, Some(b)) => b.end,
+            (None, None) => return,
+        };
 
-        for cell in range {
-            self.set(cell);
+        let mut spans: Vec<Span> = Vec::new();
+        for (first, end, a, b) in self.segments(other, 0..end) {
+            let value = f(a, b);
+            if value <= EPSILON {
+                continue;
+            }
+            match spans.last_mut() {
+                Some(ref mut last) if last.end == first && last.value == value => {
+                    last.end = end;
+                    continue;
+                }
+                _ => {}
+            };
+            spans.push(Span {
+                           first: first,
+                           end: end,
+                           value: value,
+                       });
         }
+        self.spans = spans;
     }
 
-    /// Count how many of a range of cells are set
-    pub fn count_range<'a, I>(&self, range: I) -> u32
-        where I: Iterator<Item = u32>
-    {
+    /// Get the capacity of a specific cell
+    pub fn get(&self, cell: u32) -> f32 {
+        match self.spans.get(self.find_span(cell)) {
+            Some(span) if span.first <= cell => span.value,
+            _ => 0.0,
+        }
+    }
 
-        let mut count = 0u32;
-        for cell in range {
-            if self.is_set(cell) {
-                count += 1;
-            }
+    /// Set the capacity of a specific cell
+    pub fn set_value(&mut self, cell: u32, value: f32) {
+        self.set_span(cell, cell + 1, value);
+    }
+
+    /// Set a specific cell to a capacity of one unit
+    pub fn set(&mut self, cell: u32) {
+        self.set_value(cell, 1.0);
+    }
+
+    /// Unset a specific cell
+    pub fn unset(&mut self, cell: u32) {
+        self.set_value(cell, 0.0);
+    }
+
+    /// Test whether a specific cell has any capacity
+    pub fn is_set(&self, cell: u32) -> bool {
+        self.get(cell) > EPSILON
+    }
+
+    /// Set all of the cells that are set in another row, to the larger
+    /// of the two capacities.
+    pub fn set_row(&mut self, other: &ChartTimeRow) {
+        self.combine(other, |a, b| if a > b { a } else { b });
+    }
+
+    /// Unset all of the cells that are set in another row
+    pub fn unset_row(&mut self, other: &ChartTimeRow) {
+        self.combine(other, |a, b| if b > EPSILON { 0.0 } else { a });
+    }
+
+    /// Unset all of the cells that are not set in another row
+    pub fn retain_row(&mut self, other: &ChartTimeRow) {
+        self.combine(other, |a, b| if b > EPSILON { a } else { 0.0 });
+    }
+
+    /// Add the capacity of each cell in another row to this one
+    pub fn add_row(&mut self, other: &ChartTimeRow) {
+        self.combine(other, |a, b| a + b);
+    }
+
+    /// Multiply the capacity of every cell by a factor
+    pub fn scale(&mut self, factor: f32) {
+        for span in self.spans.iter_mut() {
+            span.value *= factor;
+        }
+        self.spans.retain(|s| s.value > EPSILON);
+    }
+
+    /// Set a range of cells to a capacity of one unit
+    pub fn set_range(&mut self, range: Range<u32>) {
+        self.set_span(range.start, range.end, 1.0);
+    }
+
+    /// Count the capacity in a range of cells
+    pub fn count_range(&self, range: Range<u32>) -> f32 {
+
+        let mut count = 0.0f32;
+        for span in self.spans_in(&range) {
+            let first = if span.first > range.start { span.first } else { range.start };
+            let end = if span.end < range.end { span.end } else { range.end };
+            count += (end - first) as f32 * span.value;
         }
 
         count
     }
 
-    /// Count the number of cells that are set
-    pub fn count(&self) -> u32 {
-        let mut count = 0u32;
-        for cell in &self.cells {
-            let mut cell_copy = *cell;
-            while cell_copy != 0 {
-                if cell_copy & 0x01 == 0x01 {
-                    count += 1;
-                }
-                cell_copy >>= 1;
+    /// Get the period spanned by the cells that are set in a range.
+    ///
+    /// Returns None if none of the cells are set.
+    pub fn get_period(&self, range: Range<u32>) -> Option<ChartPeriod> {
+
+        let spans = self.spans_in(&range);
+        match (spans.first(), spans.last()) {
+            (Some(first), Some(last)) => {
+                ChartPeriod::new(if first.first > range.start { first.first } else { range.start },
+                                 if last.end < range.end { last.end } else { range.end } - 1)
             }
+            _ => None,
         }
-        count
     }
 
-    /// Transfer a number of cells to another row.  The cells are inserted
-    /// from the start of the range, as allowed by existing commitments.
+    /// Get the period from the first set cell to the last, if any are set
+    pub fn get_extent(&self) -> Option<ChartPeriod> {
+        match (self.spans.first(), self.spans.last()) {
+            (Some(first), Some(last)) => ChartPeriod::new(first.first, last.end - 1),
+            _ => None,
+        }
+    }
+
+    /// Count the capacity in all of the cells
+    pub fn count(&self) -> f32 {
+        self.spans.iter().fold(0.0, |sum, s| sum + (s.end - s.first) as f32 * s.value)
+    }
+
+    /// Work out how much can be moved from each cell in this row to the
+    /// same cell in another row, given the capacity of each.  A row never
+    /// receives more than one unit in a cell.
+    fn get_transferable(value: f32, dest_value: f32) -> f32 {
+        let space = 1.0 - dest_value;
+        let amount = if space < value { space } else { value };
+        if amount < EPSILON { 0.0 } else { amount }
+    }
+
+    /// Move an amount of capacity from each of a run of cells in this row
+    /// to another row.  The current capacity of the cells in each row
+    /// must be constant over the run.
+    fn move_span(&mut self,
+                 dest: &mut ChartTimeRow,
+                 first: u32,
+                 end: u32,
+                 value: f32,
+                 dest_value: f32,
+                 amount: f32) {
+        let remaining = value - amount;
+        self.set_span(first, end, if remaining < EPSILON { 0.0 } else { remaining });
+        dest.set_span(first, end, dest_value + amount);
+    }
+
+    /// Tidy up the amount left to transfer, so that a tiny remainder
+    /// caused by floating-point inaccuracies is treated as done.
+    fn tidy(amount: f32) -> f32 {
+        if amount < EPSILON { 0.0 } else { amount }
+    }
+
+    /// Transfer an amount of capacity to another row.  The capacity is
+    /// inserted from the start of the range, as allowed by existing
+    /// commitments.
     /// Returns a tuple of
     /// - the last cell transferred (Option)
-    /// - the number of cells transferred
-    /// - the number of cells that could not be transferred
-    pub fn fill_transfer_to<'a, I>(&mut self,
-                                   dest: &mut ChartTimeRow,
-                                   count: u32,
-                                   range: I)
-                                   -> (Option<u32>, u32, u32)
-        where I: Iterator<Item = u32>
-    {
+    /// - the amount transferred
+    /// - the amount that could not be transferred
+    pub fn fill_transfer_to(&mut self,
+                            dest: &mut ChartTimeRow,
+                            count: f32,
+                            range: Range<u32>)
+                            -> (Option<u32>, f32, f32) {
 
         let mut to_allocate = count;
         let mut last_transfer: Option<u32> = None;
 
-        for cell in range {
-            if to_allocate == 0 {
-                break;
+        let mut cell = range.start;
+        while cell < range.end && to_allocate > EPSILON {
+            let first = cell;
+            let (end, value, dest_value) = self.segment_from(dest, first, range.end);
+            cell = end;
+
+            let amount = ChartTimeRow::get_transferable(value, dest_value);
+            if amount == 0.0 {
+                continue;
             }
 
-            if self.is_set(cell) && !dest.is_set(cell) {
-                to_allocate -= 1;
-                self.unset(cell);
-                dest.set(cell);
-                last_transfer = Some(cell);
+            // Fill whole cells, then put anything left in the next cell.
+            let mut full = ((to_allocate + EPSILON) / amount) as u32;
+            if full > end - first {
+                full = end - first;
+            }
+            if full > 0 {
+                self.move_span(dest, first, first + full, value, dest_value, amount);
+                to_allocate -= full as f32 * amount;
+                last_transfer = Some(first + full - 1);
+            }
+            if full < end - first && to_allocate > EPSILON {
+                self.move_span(dest, first + full, first + full + 1, value, dest_value, to_allocate);
+                to_allocate = 0.0;
+                last_transfer = Some(first + full);
             }
         }
 
+        let to_allocate = ChartTimeRow::tidy(to_allocate);
         (last_transfer, count - to_allocate, to_allocate)
     }
 
-    /// Transfer a number of cells to another row.  The cells are inserted
-    /// from the end of the range, as allowed by existing commitments.
-    /// If not all cells can be transferred, returns an error with the number
-    /// of unallocated cells.  If successful, returns the last cell to be
-    /// transferred.
-    pub fn reverse_fill_transfer_to<'a, I>(&mut self,
-                                           dest: &mut ChartTimeRow,
-                                           count: u32,
-                                           range: I)
-                                           -> Result<u32, u32>
-        where I: std::iter::DoubleEndedIterator<Item = u32>
-    {
+    /// Transfer an amount of capacity to another row.  The capacity is
+    /// inserted from the end of the range, as allowed by existing
+    /// commitments.
+    /// Returns a tuple of
+    /// - the first cell transferred (Option), ie the earliest in the range
+    /// - the amount transferred
+    /// - the amount that could not be transferred
+    pub fn reverse_fill_transfer_to(&mut self,
+                                    dest: &mut ChartTimeRow,
+                                    count: f32,
+                                    range: Range<u32>)
+                                    -> (Option<u32>, f32, f32) {
 
         let mut to_allocate = count;
+        let mut first_transfer: Option<u32> = None;
 
-        for cell in range.rev() {
-            if self.is_set(cell) && !dest.is_set(cell) {
-                to_allocate -= 1;
-                self.unset(cell);
-                dest.set(cell);
+        let mut cell = range.end;
+        while cell > range.start && to_allocate > EPSILON {
+            let end = cell;
+            let (first, value, dest_value) = self.segment_before(dest, end, range.start);
+            cell = first;
 
-                if to_allocate == 0 {
-                    return Ok(cell);
-                }
+            let amount = ChartTimeRow::get_transferable(value, dest_value);
+            if amount == 0.0 {
+                continue;
+            }
+
+            // Fill whole cells, then put anything left in the previous cell.
+            let mut full = ((to_allocate + EPSILON) / amount) as u32;
+            if full > end - first {
+                full = end - first;
+            }
+            if full > 0 {
+                self.move_span(dest, end - full, end, value, dest_value, amount);
+                to_allocate -= full as f32 * amount;
+                first_transfer = Some(end - full);
+            }
+            if full < end - first && to_allocate > EPSILON {
+                self.move_span(dest, end - full - 1, end - full, value, dest_value, to_allocate);
+                to_allocate = 0.0;
+                first_transfer = Some(end - full - 1);
             }
         }
 
-        Err(to_allocate)
+        let to_allocate = ChartTimeRow::tidy(to_allocate);
+        (first_transfer, count - to_allocate, to_allocate)
     }
 
-    /// Transfer a number of cells to another row.  The cells are smoothed
-    /// out over the range, as much as is allowed by existing commitments.
+    /// Transfer an amount of capacity to another row.  The capacity is
+    /// smoothed out over the range, as much as is allowed by existing
+    /// commitments.
     /// Returns a tuple of
     /// - the last cell transferred (Option)
-    /// - the number of cells transferred
-    /// - the number of cells that could not be transferred
-    pub fn smear_transfer_to<'a, I>(&mut self,
-                                    dest: &mut ChartTimeRow,
-                                    count: u32,
-                                    range: I)
-                                    -> (Option<u32>, u32, u32)
-        where I: Iterator<Item = u32>
-    {
-
-        let candidate_cells = range.collect::<Vec<u32>>();
-        let mut allocated = 0u32;
-        let mut transferred_this_run = 1u32;
+    /// - the amount transferred
+    /// - the amount that could not be transferred
+    pub fn smear_transfer_to(&mut self,
+                             dest: &mut ChartTimeRow,
+                             count: f32,
+                             range: Range<u32>)
+                             -> (Option<u32>, f32, f32) {
+
+        let mut allocated = 0f32;
+        let mut transferred_this_run = 1f32;
         let mut last_transfer: Option<u32> = None;
 
-        'outer_loop: while transferred_this_run != 0 && allocated != count {
+        while transferred_this_run > EPSILON && count - allocated > EPSILON {
 
-            let mut want_allocated_this_run = 0f64;
-            let mut num_allocated_in_dest = 0u32;
-            for cell in &candidate_cells {
-                if dest.is_set(*cell) {
-                    num_allocated_in_dest += 1;
-                }
+            // Cells that are already full in the destination are skipped.
+            let segments: Vec<(u32, u32, f32, f32)> = self.segments(dest, range.clone())
+                .into_iter()
+                .filter(|s| s.3 < 1.0 - EPSILON)
+                .collect();
+            let free_cells = segments.iter().fold(0, |sum, s| sum + s.1 - s.0);
+            if free_cells == 0 {
+                break;
             }
-            let free_cells = candidate_cells.len() as u32 - num_allocated_in_dest;
-            let amount_per_cell = (count - allocated) as f64 / free_cells as f64;
-
-            transferred_this_run = 0;
-            for cell in &candidate_cells {
-
-                // Skip cells that are already allocated
-                if dest.is_set(*cell) {
+            let amount_per_cell = (count - allocated) / free_cells as f32;
+
+            // Each free cell adds to the amount wanted so far.  A cell is
+            // used whenever the amount wanted gets ahead of the amount
+            // transferred.
+            let mut want_allocated_this_run = 0f32;
+            transferred_this_run = 0.0;
+            'segment: for (first, end, value, dest_value) in segments {
+                let cells = end - first;
+                let want_before = want_allocated_this_run;
+                want_allocated_this_run += cells as f32 * amount_per_cell;
+
+                let amount = ChartTimeRow::get_transferable(value, dest_value);
+                if amount == 0.0 {
                     continue;
                 }
 
-                want_allocated_this_run += amount_per_cell;
-                // Use magic-number in the following line to
-                // avoid floating-point inaccuracies.
-                if want_allocated_this_run > 0.0001 + (transferred_this_run as f64) &&
-                   self.is_set(*cell) {
-                    allocated += 1;
-                    transferred_this_run += 1;
-                    self.unset(*cell);
-                    dest.set(*cell);
-                    match last_transfer {
-                        None => {
-                            last_transfer = Some(*cell);
+                // Work out which cells to use, as runs of adjacent cells.
+                let mut runs: Vec<(u32, u32, f32)> = Vec::new();
+                let mut ix = 1u32;
+                while ix <= cells {
+                    let behind = transferred_this_run + EPSILON - want_before;
+                    if behind > 0.0 {
+                        let skip = (behind / amount_per_cell) as u32;
+                        if skip > ix {
+                            ix = skip;
                         }
-                        Some(x) => {
-                            if x < *cell {
-                                last_transfer = Some(*cell);
-                            }
+                    }
+                    while ix <= cells &&
+                          want_before + ix as f32 * amount_per_cell <=
+                          transferred_this_run + EPSILON {
+                        ix += 1;
+                    }
+                    if ix > cells {
+                        break;
+                    }
+
+                    let cell = first + ix - 1;
+                    let this_amount = if count - allocated < amount {
+                        count - allocated
+                    } else {
+                        amount
+                    };
+                    allocated += this_amount;
+                    transferred_this_run += this_amount;
+                    match runs.last_mut() {
+                        Some(ref mut run) if run.1 == cell && run.2 == this_amount => {
+                            run.1 = cell + 1;
                         }
+                        _ => runs.push((cell, cell + 1, this_amount)),
                     };
+                    if last_transfer.map_or(true, |x| x < cell) {
+                        last_transfer = Some(cell);
+                    }
 
-                    if allocated == count {
-                        break 'outer_loop;
+                    if count - allocated < EPSILON {
+                        break;
                     }
+                    ix += 1;
+                }
+
+                for (run_first, run_end, run_amount) in runs {
+                    self.move_span(dest, run_first, run_end, value, dest_value, run_amount);
+                }
+                if count - allocated < EPSILON {
+                    break 'segment;
                 }
             }
         }
 
-        (last_transfer, allocated, count - allocated)
+        let unallocated = ChartTimeRow::tidy(count - allocated);
+        (last_transfer, count - unallocated, unallocated)
     }
 }
@@ -0,0 +1,108 @@
+use super::duration::*;
+
+/// Work that a person has done on a node, from a line of a timesheet
+#[derive(Debug, PartialEq, Clone)]
+pub struct TimesheetRow {
+    line_num: u32,
+    person: String,
+    time: String,
+    path: String,
+    duration: Duration,
+}
+
+impl TimesheetRow {
+    /// The line of the timesheet that the row is on
+    pub fn get_line_num(&self) -> u32 {
+        self.line_num
+    }
+
+    /// The person who did the work
+    pub fn get_person(&self) -> &str {
+        &self.person
+    }
+
+    /// The time that the work started, as a chart time or a date.  This
+    /// is only parsed when the chart is allocated, once the start date of
+    /// the chart is known.
+    pub fn get_time(&self) -> &str {
+        &self.time
+    }
+
+    /// The path of the node that the work was on
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    /// The amount of work
+    pub fn get_duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+/// The work that has been done, read from a CSV timesheet.
+///
+/// Each line is `<person>,<date or chart time>,<node path>,<days>`, eg
+/// `rsl,2026-10-07,PRD 12345/DCUT,0.5`.  Blank lines, and lines starting
+/// with '#', are ignored, as is a first line starting with "person".
+#[derive(Debug, PartialEq, Clone)]
+pub struct Timesheet {
+    rows: Vec<TimesheetRow>,
+    problems: Vec<String>,
+}
+
+impl Timesheet {
+    pub fn new(contents: &str) -> Timesheet {
+
+        let mut timesheet = Timesheet {
+            rows: Vec::new(),
+            problems: Vec::new(),
+        };
+
+        for (ix, line) in contents.lines().enumerate() {
+            let line_num = ix as u32 + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') ||
+               (line_num == 1 && line.starts_with("person")) {
+                continue;
+            }
+
+            let v: Vec<&str> = line.split(',').map(|x| x.trim()).collect();
+            if v.len() != 4 || v[0].is_empty() || v[1].is_empty() || v[2].is_empty() {
+                timesheet.problems
+                    .push(format!("Timesheet line {} must be <person>,<time>,<node>,<days>",
+                                  line_num));
+                continue;
+            }
+            let duration = match v[3].parse::<f32>() {
+                Ok(d) if d > 0.0 && d.is_finite() => Duration::new_days(d),
+                _ => {
+                    timesheet.problems.push(format!("Timesheet line {}: {} is not a positive \
+                                                     number of days",
+                                                    line_num,
+                                                    v[3]));
+                    continue;
+                }
+            };
+
+            timesheet.rows.push(TimesheetRow {
+                                    line_num: line_num,
+                                    person: v[0].to_string(),
+                                    time: v[1].to_string(),
+                                    path: v[2].to_string(),
+                                    duration: duration,
+                                });
+        }
+
+        timesheet
+    }
+
+    /// The rows that could be read
+    pub fn get_rows(&self) -> &Vec<TimesheetRow> {
+        &self.rows
+    }
+
+    /// A description of each line that couldn't be read
+    pub fn get_problems(&self) -> &Vec<String> {
+        &self.problems
+    }
+}
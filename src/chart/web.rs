@@ -8,9 +8,12 @@ use rocket_contrib::Template;
 use super::generate_chart_nodes;
 
 #[cfg(not(test))]
-use super::ConfigNode;
+use super::run_scenarios;
 
 #[cfg(not(test))]
+use super::ConfigNode;
+
+use super::date::*;
 use super::time::*;
 
 #[derive(Serialize)]
@@ -21,14 +24,19 @@ pub struct TemplateRow {
     done: String,
     left: String,
     plan: String,
+    budget: String,
     gain: String,
+    gain_trend: Vec<String>,
+    slack: String,
+    free_slack: String,
+    finish: String,
+    critical: bool,
     even: bool,
     notes: Vec<String>,
     notes_html: String,
     cells: Vec<(String, String)>,
 }
 
-// @@@ Display "plan", "gain"
 // @@@ Personal daily spreadsheet
 // @@@ Display "from-now", omitting completed tasks
 // @@@ Display individual PRDs
@@ -44,9 +52,15 @@ impl TemplateRow {
             who: "".to_string(),
             done: " ".to_string(),
             gain: " ".to_string(),
+            gain_trend: Vec::new(),
+            slack: " ".to_string(),
+            free_slack: " ".to_string(),
+            finish: " ".to_string(),
+            critical: false,
             line_num: line_num,
             left: " ".to_string(),
             plan: " ".to_string(),
+            budget: " ".to_string(),
             even: false,
             cells: Vec::new(),
             notes: Vec::new(),
@@ -66,13 +80,17 @@ impl TemplateRow {
         }
     }
 
-    pub fn add_cell(&mut self, val: f32, start: bool) {
+    /// Add the cell for a week.  Weeks containing holidays are shaded.
+    pub fn add_cell(&mut self, val: f32, start: bool, holiday: bool) {
         let mut styles = "grid".to_string();
         if start {
             styles.push_str(" start");
         } else if self.cells.len() == 0 {
             styles.push_str(" border");
         }
+        if holiday {
+            styles.push_str(" holiday");
+        }
 
         self.cells.push((styles, TemplateRow::format_f32(val)));
     }
@@ -89,6 +107,24 @@ impl TemplateRow {
         self.gain = TemplateRow::format_f32(gain);
     }
 
+    /// Set the total and free slack, in days.  Rows with no total
+    /// slack are on the critical path.
+    pub fn set_slack(&mut self, total: f32, free: f32) {
+        self.slack = TemplateRow::format_f32(total);
+        self.free_slack = TemplateRow::format_f32(free);
+        self.critical = total < 0.01;
+    }
+
+    /// Set the time that the work is expected to finish
+    pub fn set_finish(&mut self, finish: &ChartTime) {
+        self.finish = format!("{}", finish);
+    }
+
+    /// Set the gain at the end of each week
+    pub fn set_gain_trend(&mut self, trend: Vec<f32>) {
+        self.gain_trend = trend.iter().map(|g| format!("{}", g)).collect();
+    }
+
     pub fn set_left(&mut self, left: f32) {
         self.left = TemplateRow::format_f32(left);
     }
@@ -97,6 +133,10 @@ impl TemplateRow {
         self.plan = TemplateRow::format_f32(plan);
     }
 
+    pub fn set_budget(&mut self, budget: f32) {
+        self.budget = TemplateRow::format_f32(budget);
+    }
+
     fn prepare_html(&mut self) {
 
         self.notes_html = String::new();
@@ -119,12 +159,31 @@ impl TemplateRow {
 
 #[derive(Serialize)]
 pub struct TemplateContext {
-    cell_headers: Vec<(String, String)>,
+    /// The style, week number and start date of each week
+    cell_headers: Vec<(String, String, String)>,
     rows: Vec<TemplateRow>,
+    /// The node, kind, due time, expected time and days missed of each
+    /// deadline
+    deadlines: Vec<(String, String, String, String, String)>,
+    /// The results of the optimiser, if it was used
+    optimiser: Vec<String>,
+    /// The names of the what-if scenarios
+    scenarios: Vec<String>,
+    /// The measure, then the baseline and scenario values, of each row
+    /// of the scenario comparison
+    comparison: Vec<(String, Vec<String>)>,
+    /// Whether there are scenarios that weren't run, to link to
+    scenarios_link: bool,
+    /// How the chart was simulated, if it was, and the percentiles shown
+    simulation_heading: String,
+    simulation_percentiles: Vec<String>,
+    /// The node, measure and value at each percentile of each simulation
+    /// result
+    simulation: Vec<(String, String, Vec<String>)>,
 }
 
 impl TemplateContext {
-    pub fn new(cells: u32, start_cell: u32) -> TemplateContext {
+    pub fn new(cells: u32, start_cell: u32, start_date: Option<ChartDate>) -> TemplateContext {
         TemplateContext {
             cell_headers: (1..cells + 1)
                 .map(|s| {
@@ -135,10 +194,20 @@ impl TemplateContext {
                     } else {
                         "grid".to_string()
                     },
-                     format!("{}", s))
+                     format!("{}", s),
+                     start_date.map_or(String::new(),
+                                       |d| format!("{}", d.add_days((s as i64 - 1) * 7))))
                 })
                 .collect(),
             rows: Vec::new(),
+            deadlines: Vec::new(),
+            optimiser: Vec::new(),
+            scenarios: Vec::new(),
+            comparison: Vec::new(),
+            scenarios_link: false,
+            simulation_heading: String::new(),
+            simulation_percentiles: Vec::new(),
+            simulation: Vec::new(),
         }
     }
 
@@ -147,6 +216,54 @@ impl TemplateContext {
         self.rows.push(row);
     }
 
+    /// Add a deadline to the summary.  The expected time is None if
+    /// there's no work.
+    pub fn add_deadline(&mut self,
+                        what: &str,
+                        kind: &str,
+                        due: &ChartTime,
+                        projected: Option<ChartTime>,
+                        missed: f32) {
+        self.deadlines.push((what.to_string(),
+                             kind.to_string(),
+                             format!("{}", due),
+                             projected.map_or(String::new(), |p| format!("{}", p)),
+                             TemplateRow::format_f32(missed)));
+    }
+
+    /// Add a line to the results of the optimiser
+    pub fn add_optimiser_line(&mut self, line: &str) {
+        self.optimiser.push(line.to_string());
+    }
+
+    /// Add a what-if scenario to the comparison
+    pub fn add_scenario(&mut self, name: &str) {
+        self.scenarios.push(name.to_string());
+    }
+
+    /// Link to the page that compares the what-if scenarios, as they
+    /// haven't been run.
+    pub fn set_scenarios_link(&mut self) {
+        self.scenarios_link = true;
+    }
+
+    /// Add a row to the scenario comparison, with the baseline value
+    /// followed by the value for each scenario.
+    pub fn add_comparison(&mut self, what: &str, values: Vec<String>) {
+        self.comparison.push((what.to_string(), values));
+    }
+
+    /// Describe the simulation, and the percentiles of the results
+    pub fn set_simulation(&mut self, runs: u32, seed: u64, percentiles: &[u32]) {
+        self.simulation_heading = format!("Simulated {} runs, with seed {}", runs, seed);
+        self.simulation_percentiles = percentiles.iter().map(|p| format!("P{}", p)).collect();
+    }
+
+    /// Add a result from the simulation, with its value at each percentile
+    pub fn add_simulation_result(&mut self, what: &str, measure: &str, values: Vec<String>) {
+        self.simulation.push((what.to_string(), measure.to_string(), values));
+    }
+
     fn prepare_html(&mut self) {
         for row in &mut self.rows {
             row.prepare_html();
@@ -155,16 +272,26 @@ impl TemplateContext {
 }
 
 
+/// Generate the page for a chart.  The what-if scenarios are only run,
+/// and compared with the chart, if compare is set, as each of them
+/// allocates the chart again.
 #[cfg(not(test))]
-fn generate_chart_html(root: &mut ConfigNode) -> Result<Template, String> {
+fn generate_chart_html(root: &mut ConfigNode, compare: bool) -> Result<Template, String> {
 
     let weeks: u32 = try!(root.get_config_val("weeks", None));
-    let start: ChartTime = try!(root.get_config_val("today", Some(ChartTime::new("1").unwrap())));
+    let start: ChartTime = try!(root.get_today());
     let start_week = (start.get_quarter() + 20) / 20;
 
-    let mut context = TemplateContext::new(weeks, start_week);
+    let mut context = TemplateContext::new(weeks, start_week, try!(root.get_start_date()));
     try!(root.display_gantt(&mut context));
 
+    if compare {
+        let scenarios = try!(run_scenarios(root));
+        root.display_scenarios(&scenarios, &mut context);
+    } else if root.get_scenarios().len() > 0 {
+        context.set_scenarios_link();
+    }
+
     // Do any required preparation before rendering
     context.prepare_html();
 
@@ -184,13 +311,12 @@ fn generate_error_html(err: &str) -> Template {
 }
 
 #[cfg(not(test))]
-#[get("/")]
-fn index() -> Template {
+fn generate_page(compare: bool) -> Template {
 
-    match generate_chart_nodes() {
+    match generate_chart_nodes(None) {
         Ok(rc_root) => {
             let mut root = rc_root.borrow_mut();
-            match generate_chart_html(&mut root) {
+            match generate_chart_html(&mut root, compare) {
                 Ok(template) => {
                     return template;
                 }
@@ -205,7 +331,19 @@ fn index() -> Template {
     };
 }
 
+#[cfg(not(test))]
+#[get("/")]
+fn index() -> Template {
+    generate_page(false)
+}
+
+#[cfg(not(test))]
+#[get("/scenarios")]
+fn scenarios() -> Template {
+    generate_page(true)
+}
+
 #[cfg(not(test))]
 pub fn serve_web() {
-    rocket::ignite().mount("/", routes![index]).launch();
+    rocket::ignite().mount("/", routes![index, scenarios]).launch();
 }
@@ -0,0 +1,181 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// The people who work on a task
+///
+/// This is given by the "who" attribute, in one of these forms.
+/// - A single person, eg `rsl`.
+/// - A list of people who split the work evenly, eg `rsl, jw2`.
+/// - A list of people who split the work in the given ratios, eg
+///   `rsl 70%, jw2 30%`.  The shares must add up to 100%.
+/// - A pool of people, eg `pool(rsl, jw2)`.  The work is done by
+///   whoever in the pool is free first, earlier people in the list
+///   being preferred.
+/// - Any one person with a role, eg `any(backend)`.  The person is
+///   chosen when the task is allocated (see PersonConfig for roles).
+#[derive(Debug, PartialEq, Clone)]
+pub struct Who {
+    people: Vec<String>,
+    shares: Option<Vec<f32>>,
+    pooled: bool,
+    role: Option<String>,
+}
+
+impl fmt::Display for Who {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.role {
+            Some(ref role) => return write!(f, "any({})", role),
+            None => {}
+        };
+        let people: Vec<String> = match self.shares {
+            Some(ref shares) => {
+                self.people
+                    .iter()
+                    .zip(shares.iter())
+                    .map(|(p, s)| format!("{} {}%", p, s))
+                    .collect()
+            }
+            None => self.people.clone(),
+        };
+        if self.pooled {
+            write!(f, "pool({})", people.join(", "))
+        } else {
+            write!(f, "{}", people.join(", "))
+        }
+    }
+}
+
+impl FromStr for Who {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Who::new(s)
+    }
+}
+
+impl Who {
+    pub fn new(desc: &str) -> Result<Who, String> {
+
+        let mut slice = desc.trim();
+        if slice.starts_with("any(") && slice.ends_with(')') {
+            let role = slice[4..slice.len() - 1].trim();
+            if role.len() == 0 || role.contains(',') || role.contains(' ') {
+                return Err(format!("Invalid role in {}", desc));
+            }
+            return Ok(Who {
+                          people: Vec::new(),
+                          shares: None,
+                          pooled: false,
+                          role: Some(role.to_string()),
+                      });
+        }
+
+        let mut pooled = false;
+        if slice.starts_with("pool(") && slice.ends_with(')') {
+            pooled = true;
+            slice = &slice[5..slice.len() - 1];
+        }
+
+        let mut people: Vec<String> = Vec::new();
+        let mut shares: Vec<f32> = Vec::new();
+        for part in slice.split(',') {
+            let v: Vec<&str> = part.split_whitespace().collect();
+            if v.len() == 0 || v.len() > 2 {
+                return Err(format!("Invalid person \"{}\" in {}", part.trim(), desc));
+            }
+            if people.contains(&v[0].to_string()) {
+                return Err(format!("{} is listed more than once in {}", v[0], desc));
+            }
+            people.push(v[0].to_string());
+
+            if v.len() == 2 {
+                if !v[1].ends_with('%') {
+                    return Err(format!("Share {} must be a percentage in {}", v[1], desc));
+                }
+                let share = try!(v[1][..v[1].len() - 1]
+                    .parse::<f32>()
+                    .map_err(|e| format!("Invalid share {} in {}: {}", v[1], desc, e)));
+                if share <= 0.0 {
+                    return Err(format!("Share {} must be positive in {}", v[1], desc));
+                }
+                shares.push(share);
+            }
+        }
+
+        let shares = match shares.len() {
+            0 => None,
+            _ if pooled => {
+                return Err(format!("A pool can't have shares: {}", desc));
+            }
+            n if n != people.len() => {
+                return Err(format!("Either all or none of the people need a share in {}",
+                                   desc));
+            }
+            _ => {
+                let total = shares.iter().fold(0.0, |sum, s| sum + s);
+                if (total - 100.0).abs() > 0.01 {
+                    return Err(format!("Shares add up to {}%, not 100%, in {}", total, desc));
+                }
+                Some(shares)
+            }
+        };
+
+        Ok(Who {
+               people: people,
+               shares: shares,
+               pooled: pooled,
+               role: None,
+           })
+    }
+
+    /// Create an owner who is a single person
+    pub fn new_person(who: &str) -> Who {
+        Who {
+            people: vec![who.to_string()],
+            shares: None,
+            pooled: false,
+            role: None,
+        }
+    }
+
+    /// Get the role that the owner must have, if it is any person with
+    /// that role.
+    pub fn get_role(&self) -> Option<&String> {
+        self.role.as_ref()
+    }
+
+    /// Get all of the people, in the order they were listed.  This is
+    /// empty if the owner is any person with a role.
+    pub fn get_people(&self) -> &Vec<String> {
+        &self.people
+    }
+
+    /// Whether the work is done by a pool of people
+    pub fn is_pooled(&self) -> bool {
+        self.pooled
+    }
+
+    /// Split the work between the people.
+    ///
+    /// Returns groups of people that share the work between them, along
+    /// with the fraction of the work that each group does.  A pool is a
+    /// single group; otherwise each person is a group on their own.
+    pub fn get_groups(&self) -> Vec<(Vec<String>, f32)> {
+        if self.pooled {
+            return vec![(self.people.clone(), 1.0)];
+        }
+
+        let count = self.people.len() as f32;
+        self.people
+            .iter()
+            .enumerate()
+            .map(|(ix, p)| {
+                let share = match self.shares {
+                    Some(ref shares) => shares[ix] / 100.0,
+                    None => 1.0 / count,
+                };
+                (vec![p.clone()], share)
+            })
+            .collect()
+    }
+}